@@ -7,8 +7,17 @@ use std::{f32, fs::{
     Write
 }, path::Path};
 use gep2_lib::{
-    fitness_evaluator::FitnessFunction,
-    population::Population,
+    fitness_evaluator::{Costs, FitnessEvaluator, FitnessFunction},
+    objectives::{self, ObjectiveEvaluator},
+    pareto::TradingCriteria,
+    selection,
+    supervised::{ClassificationEvaluator, ClassificationMetric, RegressionEvaluator, RegressionMetric},
+    population::{Checkpoint, OperatorRates, Population, Rates, Settings},
+    schedule::Schedule,
+    chromosome::{Chromosome, CrossoverMode},
+    expression::Format,
+    function_set::FunctionSet,
+    linking_function,
     statistics::Statistics,
     config::Config
 };
@@ -16,38 +25,238 @@ use chrono::prelude::*;
 
 fn main() {
     // setup
-    let toml_config_str = read_to_string("./driver_config.toml").unwrap();
-    let conf: Config = toml::from_str(&toml_config_str).unwrap();
+    let mut toml_config_str = read_to_string("./driver_config.toml").unwrap();
+    let mut conf: Config = toml::from_str(&toml_config_str).unwrap();
+    // a resumed search is set up by the config saved with its checkpoint
+    let mut checkpoint = None;
+    if let Some(ref path) = conf.resume {
+        let (config_str, c) = load_checkpoint(path);
+        let passes = conf.passes;
+        conf = toml::from_str(&config_str).unwrap();
+        conf.passes = passes;
+        toml_config_str = config_str;
+        checkpoint = Some(c);
+    }
+
+    let mut fs = match conf.functions {
+        Some(ref symbols) => FunctionSet::from_symbols(symbols).expect("unknown function symbol in config"),
+        None => FunctionSet::default(),
+    };
+    if let Some(ref windows) = conf.windows { fs.set_windows(windows) }
+    if let Some(constants) = conf.constants {
+        let [lo, hi] = conf.constant_range.unwrap_or([-1.0, 1.0]);
+        fs.set_constants(constants, lo, hi);
+    }
+    let mut lf = linking_function::from_symbol(conf.linking_function.as_deref().unwrap_or("Maj"))
+        .expect("unknown linking function in config");
+    if conf.continuous_position.unwrap_or(false) {
+        lf = linking_function::continuous(lf, conf.position_levels.unwrap_or(0));
+    }
+    let p = &mut Population::new(Settings {
+        size: conf.population_size,
+        gene_nbr: conf.nbr_of_genes,
+        head_length: conf.head_length,
+        num_args: conf.inputs_cnt,
+        max_delay: conf.max_delay,
+        transposition_probability: conf.transposition_probability,
+        mutation_probability: conf.mutation_probability,
+        function_set: fs,
+        linking_function: lf,
+        seed: conf.seed,
+    });
+    if let Some(threads) = conf.threads { p.set_threads(threads) }
+    if let Some(homeotic) = conf.homeotic_genes {
+        p.set_homeotic(homeotic, conf.homeotic_head_length.unwrap_or(conf.head_length));
+    }
+    if let Some(ref s) = conf.selection {
+        p.set_selection(selection::from_symbol(s).expect("unknown selection in config"));
+    }
+    if let Some(elite_count) = conf.elite_count { p.set_elite_count(elite_count) }
+    let crossover = CrossoverMode::from_symbol(conf.crossover.as_deref().unwrap_or("Free")).expect("unknown crossover in config");
+    p.set_crossover(crossover, conf.crossover_probability.unwrap_or(1.0));
+    p.set_operator_rates(OperatorRates {
+        is_transposition: conf.is_transposition_probability.unwrap_or(0.0),
+        gene_transposition: conf.gene_transposition_probability.unwrap_or(0.0),
+        inversion: conf.inversion_probability.unwrap_or(0.0),
+        one_point_crossover: conf.one_point_crossover_probability.unwrap_or(0.0),
+        gene_recombination: conf.gene_recombination_probability.unwrap_or(0.0),
+        dc_transposition: conf.dc_transposition_probability.unwrap_or(0.0),
+        constant_mutation: conf.constant_mutation_probability.unwrap_or(0.0),
+    });
+    for (operator, s) in conf.schedules.iter().flatten() {
+        let schedule = Schedule::from_symbol(s).expect("unknown schedule in config");
+        assert!(p.set_schedule(operator, schedule), "unknown operator '{}' in schedules", operator);
+    }
+    if let Some(every) = conf.checkpoint_every {
+        let path = conf.checkpoint_path.clone().unwrap_or_else(|| "./checkpoint.toml".to_string());
+        p.set_checkpoints(every, Box::new(move |c| save_checkpoint(&path, &toml_config_str, c)));
+    }
+
+    let mode = conf.mode.as_deref().unwrap_or("trading");
+    p.set_target_fitness(conf.target_fitness.or(if mode == "trading" {Some(0.95)} else {None}));
+    match mode {
+        "trading" => run_trading(&conf, p, checkpoint),
+        "regression" | "classification" => run_supervised(&conf, p, checkpoint),
+        m => panic!("unknown mode '{}' in config", m),
+    }
+}
 
-    let v = read_data(conf.data_path);
+fn run_trading(conf: &Config, p: &mut Population, checkpoint: Option<Checkpoint>) {
+    let v = read_data(&conf.data_path);
     let mut deltas = Vec::with_capacity(v.len()-1);
     for i in 1..v.len(){ deltas.push(v[i] - v[i-1]) }
-    let fe = get_fitness_function(deltas, conf.inputs_cnt, conf.train_fraction);
+    let mut fe = get_fitness_function(deltas, conf.inputs_cnt, conf.train_fraction);
+    fe.set_costs(Costs::new(conf.commission.unwrap_or(0.0), conf.slippage.unwrap_or(0.0),
+                            conf.proportional_slippage.unwrap_or(0.0)));
+    if let Some(ref criteria) = conf.pareto {
+        assert!(checkpoint.is_none(), "a Pareto search cannot be resumed");
+        let tc = TradingCriteria::from_symbols(fe, criteria).expect("unknown criterion in pareto");
+        let front = p.search_pareto(&tc, conf.passes);
+        save_front(&tc, p, &front);
+        return;
+    }
+    let fe: Box<dyn FitnessEvaluator> = match conf.objective.as_deref() {
+        None | Some("Equity") => Box::new(fe),
+        Some("Comp") => {
+            let weights: Vec<(String, f32)> = conf.objective_weights.clone().unwrap_or_default().into_iter().collect();
+            Box::new(ObjectiveEvaluator::new(fe, objectives::composite(&weights).expect("unknown objective in objective_weights")))
+        }
+        Some(s) => Box::new(ObjectiveEvaluator::new(fe, objectives::from_symbol(s).expect("unknown objective in config"))),
+    };
 
-    let p = &mut Population::new(conf.population_size, conf.nbr_of_genes, conf.head_length,
-                                 conf.inputs_cnt, conf.max_delay,
-                                 conf.transposition_probability, conf.mutation_probability);
     // search
-    let stat = &p.search(&fe, conf.passes);
+    let stat = &search(p, fe.as_ref(), conf.passes, checkpoint);
+
+    // reporting
+    save_results(conf, fe.as_ref(), p, stat);
+}
+
+// data rows: inputs_cnt inputs followed by the target
+fn run_supervised(conf: &Config, p: &mut Population, checkpoint: Option<Checkpoint>) {
+    let (matrix, targets) = read_table(&conf.data_path, conf.inputs_cnt);
+    let metric = conf.metric.as_deref();
+    let path = results_dir();
+    if conf.mode.as_deref() == Some("regression") {
+        let metric = RegressionMetric::from_symbol(metric.unwrap_or("MSE")).expect("unknown metric in config");
+        let fe = RegressionEvaluator::new(matrix, targets, conf.train_fraction, metric);
+        let stat = &search(p, &fe, conf.passes, checkpoint);
+        save_model(conf, &path, &p.chromosomes[stat[stat.len() - 1].3], p.max_delay);
+        let mut program = p.chromosomes[stat[stat.len() - 1].3].translate();
+        save_report(&path, "train", &fe.report(p.max_delay, true, &mut program), &fe.outputs(p.max_delay, true, &mut program));
+        save_report(&path, "test", &fe.report(p.max_delay, false, &mut program), &fe.outputs(p.max_delay, false, &mut program));
+    } else {
+        let metric = ClassificationMetric::from_symbol(metric.unwrap_or("Acc")).expect("unknown metric in config");
+        let fe = ClassificationEvaluator::new(matrix, targets, conf.train_fraction,
+                                              conf.classes.unwrap_or(2), conf.hit_threshold.unwrap_or(0.5), metric);
+        let stat = &search(p, &fe, conf.passes, checkpoint);
+        save_model(conf, &path, &p.chromosomes[stat[stat.len() - 1].3], p.max_delay);
+        let mut program = p.chromosomes[stat[stat.len() - 1].3].translate();
+        save_report(&path, "train", &fe.report(p.max_delay, true, &mut program), &fe.outputs(p.max_delay, true, &mut program));
+        save_report(&path, "test", &fe.report(p.max_delay, false, &mut program), &fe.outputs(p.max_delay, false, &mut program));
+    }
+}
+
+// a new search or the one of the checkpoint continued for `passes` more passes
+fn search(p: &mut Population, fe: &dyn FitnessEvaluator, passes: usize, checkpoint: Option<Checkpoint>) -> Vec<(f32, f32, String, usize, Rates)> {
+    let stat = match checkpoint {
+        Some(c) => {
+            println!("resuming at pass {}", c.generation);
+            p.resume(fe, c, passes).expect("checkpoint does not fit population_size")
+        }
+        None => p.search(fe, passes),
+    };
     for i in 0..stat.len(){
         println!("({}) - max. fitness : {}, avg. fitness : {}", i+1, stat[i].0, stat[i].1);
+        // scheduled rates
+        if stat[i].4 != stat[0].4 { println!("    rates : {:?}", stat[i].4) }
     }
     println!("{}", stat[stat.len()-1].2);
-
-    // reporting
-    save_results(&fe, p, stat);
+    println!("{}", p.chromosomes[stat[stat.len()-1].3].expression().render(Format::Text));
+    stat
 }
 
-fn save_results(fe: &FitnessFunction, p: &mut Population, stat: &Vec<(f32, f32, String, usize)>) {
+fn results_dir() -> String {
     const DATE_FORMAT_STR: &'static str = "%Y%m%d%H%M%S";
+    let path = format!("./results_{}", Utc::now().format(DATE_FORMAT_STR).to_string());
+    fs::create_dir(&path).expect("Unable to create results directory");
+    fs::copy("./driver_config.toml", format!("{}/{}", path, "driver_config.toml")).expect("Unable to copy config");
+    path
+}
+
+// objectives and k-string of every member of the Pareto front, equity of every member
+fn save_front(tc: &TradingCriteria, p: &Population, front: &[(String, Vec<f32>, usize)]) {
+    let path = results_dir();
+    let mut f = File::create(format!("{}/{}", path, "pareto.txt")).expect("Unable to create file");
+    for (n, (k, objs, i)) in front.iter().enumerate() {
+        println!("{:?} {}", objs, k);
+        writeln!(&mut f, "{:?} {}", objs, k).unwrap();
+        let mut f = File::create(format!("{}/test_eqt_{}.txt", path, n)).expect("Unable to create file");
+        for e in p.chromosomes[*i].equity(p.max_delay, false, &tc.market) {
+            writeln!(&mut f, "{},", e).unwrap();
+        }
+    }
+}
+
+// the config and the checkpoint, written to a temporary file first so that a killed run
+// keeps the previous checkpoint
+fn save_checkpoint(path: &str, config: &str, checkpoint: &Checkpoint) {
+    let mut t = toml::value::Table::new();
+    t.insert("config".to_string(), toml::Value::String(config.to_string()));
+    t.insert("checkpoint".to_string(), toml::Value::try_from(checkpoint).unwrap());
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, toml::to_string(&toml::Value::Table(t)).unwrap()).expect("Unable to write checkpoint");
+    fs::rename(&tmp, path).expect("Unable to write checkpoint");
+}
+
+fn load_checkpoint(path: &str) -> (String, Checkpoint) {
+    let mut t: toml::value::Table = toml::from_str(&read_to_string(path).expect("Unable to read checkpoint"))
+        .expect("invalid checkpoint");
+    let config = t.remove("config").and_then(|c| c.as_str().map(String::from)).expect("no config in checkpoint");
+    let checkpoint = t.remove("checkpoint").expect("invalid checkpoint").try_into().expect("invalid checkpoint");
+    (config, checkpoint)
+}
+
+// the best chromosome, to be reloaded and re-evaluated later, and its expression in the configured formats
+fn save_model(conf: &Config, path: &str, chr: &Chromosome, max_delay: usize) {
+    let mut f = File::create(format!("{}/{}", path, "best.toml")).expect("Unable to create file");
+    write!(&mut f, "{}", toml::to_string(chr).unwrap()).unwrap();
+    let formats = conf.expression_formats.clone().unwrap_or_else(|| vec!["Text".to_string()]);
+    for s in formats {
+        let format = Format::from_symbol(&s).expect("unknown format in expression_formats");
+        let mut f = File::create(format!("{}/model.{}", path, format.extension())).expect("Unable to create file");
+        // Rust models are standalone structs unless they use user functions
+        let module = if format == Format::Rust {chr.to_rust("Model", max_delay)} else {None};
+        match module {
+            Some(m) => write!(&mut f, "{}", m).unwrap(),
+            None => writeln!(&mut f, "{}", chr.expression().render(format)).unwrap(),
+        }
+    }
+}
+
+// metrics and outputs of the best chromosome on train or test data
+fn save_report(path: &str, name: &str, report: &[(&'static str, f32)], outputs: &[f32]) {
+    let mut f = File::create(format!("{}/{}_out.txt", path, name)).expect("Unable to create file");
+    for o in outputs {
+        writeln!(&mut f, "{},", o).unwrap();
+    }
+    let mut f = File::create(format!("{}/{}_stat.txt", path, name)).expect("Unable to create file");
+    for (metric, value) in report {
+        writeln!(&mut f, "{} = {}", metric, value).unwrap();
+    }
+}
+
+// trade counts skip only bars that stay flat, the bar that closes a position is counted
+const STAT_HEADER: &str = "# count, percent_profitable, avg_win and avg_loss cover bars where a position was held or changed\n";
+
+fn save_results(conf: &Config, fe: &dyn FitnessEvaluator, p: &mut Population, stat: &Vec<(f32, f32, String, usize, Rates)>) {
     let chr = &p.chromosomes[stat[stat.len() - 1].3];
     let train_equity = chr.equity(p.max_delay, true, fe);
-    let train_stat = Statistics::new(&train_equity);
+    let train_stat = Statistics::new(&train_equity, &chr.outputs(p.max_delay, true, fe));
     let test_equity = chr.equity(p.max_delay, false, fe);
-    let test_stat = Statistics::new(&test_equity);
+    let test_stat = Statistics::new(&test_equity, &chr.outputs(p.max_delay, false, fe));
 
-    let path = format!("./results_{}", Utc::now().format(DATE_FORMAT_STR).to_string());
-    fs::create_dir(&path);
+    let path = results_dir();
+    save_model(conf, &path, chr, p.max_delay);
 
     let mut f = File::create(format!("{}/{}", path, "train_eqt.txt")).expect("Unable to create file");
     for i in 0..train_equity.len() {
@@ -59,15 +268,13 @@ fn save_results(fe: &FitnessFunction, p: &mut Population, stat: &Vec<(f32, f32,
     }
     let mut f = File::create(format!("{}/{}", path, "train_stat.txt")).expect("Unable to create file");
     let toml = toml::to_string(&train_stat).unwrap();
-    write!(&mut f, "{}", toml);
+    write!(&mut f, "{}{}", STAT_HEADER, toml).unwrap();
     let mut f = File::create(format!("{}/{}", path, "test_stat.txt")).expect("Unable to create file");
     let toml = toml::to_string(&test_stat).unwrap();
-    write!(&mut f, "{}", toml);
-
-    fs::copy("./driver_config.toml", format!("{}/{}", path, "driver_config.toml"));
+    write!(&mut f, "{}{}", STAT_HEADER, toml).unwrap();
 }
 
-pub fn read_data(path: String)->Vec<f32>{
+pub fn read_data(path: &str)->Vec<f32>{
     let mut data = Vec::new();
     if let Ok(lines) = read_lines(path) {
         for line in lines {
@@ -80,6 +287,25 @@ pub fn read_data(path: String)->Vec<f32>{
     data
 }
 
+// rows of comma separated values: inputs_cnt inputs and the target, blank lines are skipped
+pub fn read_table(path: &str, inputs_cnt: usize) -> (Vec<Vec<f32>>, Vec<f32>) {
+    let mut matrix = Vec::new();
+    let mut targets = Vec::new();
+    let lines = read_lines(path).unwrap_or_else(|e| panic!("Unable to open {}: {}", path, e));
+    for (n, line) in lines.enumerate() {
+        let ip = line.unwrap_or_else(|e| panic!("Unable to read line {} of {}: {}", n + 1, path, e));
+        if ip.trim().is_empty() {continue}
+        let split:Vec<f32> = ip.as_str().split(",")
+            .map(|v| v.trim().parse::<f32>().unwrap_or_else(|e| panic!("line {} of {}: '{}': {}", n + 1, path, v.trim(), e)))
+            .collect();
+        assert!(split.len() > inputs_cnt, "line {} of {}: {} values, expected {} inputs and the target",
+                n + 1, path, split.len(), inputs_cnt);
+        matrix.push(split[..inputs_cnt].to_vec());
+        targets.push(split[inputs_cnt]);
+    }
+    (matrix, targets)
+}
+
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>> where P: AsRef<Path> {
     let file = File::open(filename)?;
     Ok(io::BufReader::new(file).lines())
@@ -1,73 +1,290 @@
-use std::cell::Cell;
-use rand::prelude::ThreadRng;
+use std::sync::Arc;
 use rand::Rng;
 use crate::codons::Codon;
-use crate::delay_line::DelayLine;
 use crate::feedback::Feedback;
-use crate::linking_function::LF;
-use crate::functions::{FN_NUM, Function, FREGISTRY};
-use crate::state_functions::{SFN_NUM, StateFunction, SFREGISTRY};
+use crate::linking_function::LinkingFunction;
+use crate::program::Program;
+use crate::expression::Expression;
+use crate::codegen;
+use crate::function_set::FunctionSet;
 use crate::terminal::Terminal;
 use crate::fitness_evaluator::FitnessEvaluator;
+use crate::pareto::MultiObjectiveEvaluator;
+use std::sync::OnceLock;
+use serde::{de, de::DeserializeSeed, Deserialize, Deserializer, Serialize, Serializer};
+
+// where two-point crossover may cut the chromosomes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CrossoverMode {
+    // anywhere
+    Free,
+    // at gene boundaries only, whole genes are swapped
+    Genes,
+    // both points within the same gene
+    InGene,
+}
+
+impl CrossoverMode {
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "Free" => Some(CrossoverMode::Free),
+            "Genes" => Some(CrossoverMode::Genes),
+            "InGene" => Some(CrossoverMode::InGene),
+            _ => None,
+        }
+    }
+}
+
+// serialized form of a chromosome: the linking function by symbol, codons by k-string token
+// (see Codon::token); loading checks that the parts fit together
+#[derive(Serialize, Deserialize)]
+struct ChromosomeData {
+    linker: String,
+    head_size: usize,
+    tail_size: usize,
+    nbr_of_genes: usize,
+    // symbols of user (registered) functions among the codons, only WithFunctionSet loads them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    user_functions: Vec<String>,
+    codons: Vec<String>,
+    #[serde(default)]
+    weights: Vec<f32>,
+    #[serde(default)]
+    dc: Vec<usize>,
+    #[serde(default)]
+    constants: Vec<f32>,
+    #[serde(default)]
+    homeotic_head: usize,
+    #[serde(default)]
+    homeotic_tail: usize,
+    #[serde(default)]
+    homeotic_nbr: usize,
+    #[serde(default)]
+    homeotic: Vec<String>,
+    #[serde(default)]
+    fitness: f32,
+}
+
+impl Serialize for Chromosome {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ChromosomeData {
+            linker: self.linker.symbol(),
+            head_size: self.head_size,
+            tail_size: self.tail_size,
+            nbr_of_genes: self.nbr_of_genes,
+            user_functions: self.user_functions(),
+            codons: self.codons.iter().map(Codon::token).collect(),
+            weights: self.weights.clone(),
+            dc: self.dc.clone(),
+            constants: self.constants.clone(),
+            homeotic_head: self.homeotic_head,
+            homeotic_tail: self.homeotic_tail,
+            homeotic_nbr: self.homeotic_nbr,
+            homeotic: self.homeotic.iter().map(Codon::token).collect(),
+            fitness: self.fitness,
+        }.serialize(serializer)
+    }
+}
+
+impl ChromosomeData {
+    // built-in functions are resolved against all built-ins, user functions against fs
+    fn into_chromosome<E: de::Error>(self, fs: Option<&FunctionSet>) -> Result<Chromosome, E> {
+        static BUILTINS: OnceLock<FunctionSet> = OnceLock::new();
+        let builtins = BUILTINS.get_or_init(FunctionSet::builtins);
+        let codon = |token: &String| -> Result<Codon, E> {
+            if !self.user_functions.contains(token) {
+                return builtins.codon(token).ok_or_else(|| E::custom(format!("unknown codon '{}'", token)));
+            }
+            let fs = fs.ok_or_else(|| E::custom(format!("user function '{}' needs its function set (WithFunctionSet)", token)))?;
+            match fs.codon(token) {
+                Some(Codon::Function(f)) if !f.fd.builtin => Ok(Codon::Function(f)),
+                _ => Err(E::custom(format!("no user function '{}' in the function set", token))),
+            }
+        };
+        let codons = self.codons.iter().map(codon).collect::<Result<Vec<Codon>, E>>()?;
+        let homeotic = self.homeotic.iter().map(codon).collect::<Result<Vec<Codon>, E>>()?;
+        let linker = crate::linking_function::from_symbol(&self.linker)
+            .ok_or_else(|| E::custom(format!("unknown linking function '{}'", self.linker)))?;
+        let c = Chromosome {
+            head_size: self.head_size,
+            tail_size: self.tail_size,
+            nbr_of_genes: self.nbr_of_genes,
+            linker,
+            homeotic_head: self.homeotic_head,
+            homeotic_tail: self.homeotic_tail,
+            homeotic_nbr: self.homeotic_nbr,
+            homeotic,
+            codons,
+            weights: self.weights,
+            dc: self.dc,
+            constants: self.constants,
+            fitness: self.fitness,
+        };
+        if c.is_consistent() {Ok(c)} else {Err(E::custom("inconsistent chromosome"))}
+    }
+}
+
+// chromosomes with user functions are refused, see WithFunctionSet
+impl<'de> Deserialize<'de> for Chromosome {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ChromosomeData::deserialize(deserializer)?.into_chromosome(None)
+    }
+}
+
+// loads a chromosome whose user functions are registered in the function set, e.g.
+// `WithFunctionSet(&fs).deserialize(&mut toml::Deserializer::new(&text))`
+pub struct WithFunctionSet<'a>(pub &'a FunctionSet);
+
+impl<'de> DeserializeSeed<'de> for WithFunctionSet<'_> {
+    type Value = Chromosome;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Chromosome, D::Error> {
+        ChromosomeData::deserialize(deserializer)?.into_chromosome(Some(self.0))
+    }
+}
 
 pub struct Chromosome {
     head_size:usize,
+    // h*(n_max-1)+1, so that any head can be completed by tail terminals
+    tail_size:usize,
     nbr_of_genes:usize,
+    linker: Arc<dyn LinkingFunction>,
+    // homeotic genes (GEP-ADF), none by default: the genes above are ADFs, terminal i of a homeotic
+    // gene is the output of ADF i and the linking function combines outputs of homeotic genes
+    homeotic_head: usize,
+    homeotic_tail: usize,
+    homeotic_nbr: usize,
+    pub homeotic: Vec<Codon>,
 
     pub codons: Vec<Codon>,
-    // to hide mutability
-    pub fitness: Cell<f32>,
+    // one per gene (per homeotic gene if there are any), used by weighted linking functions only
+    // (empty otherwise)
+    pub weights: Vec<f32>,
+    // GEP-RNC (empty without constants): Dc domain of every gene (tail_size indexes into the constants
+    // of the gene, the n-th `?` of a gene reads the n-th index) and the constants, fs.constants() per gene
+    pub dc: Vec<usize>,
+    pub constants: Vec<f32>,
+    pub fitness: f32,
+}
+// genes or homeotic genes an operator works on, target_gene among them
+struct Domain<'a> {
+    codons: &'a mut Vec<Codon>,
+    target_gene: usize,
+    genes: usize,
+    head_size: usize,
+    gene_length: usize,
+}
+
+// shape of the genes drawn by initialization
+#[derive(Clone, Copy)]
+struct Layout {
+    genes: usize,
+    head: usize,
+    tail: usize,
+    args: usize,
+    max_delay: usize,
 }
+
 impl Chromosome{
-    // initialization
-    pub fn new(mut rng: &mut ThreadRng, mut gene_nbr:usize, mut head_length: usize, mut args_nbr:usize, max_delay: usize) -> Self{
+    // initialization; panics if the function set is empty
+    pub fn new<R: Rng + ?Sized>(rng: &mut R, fs: &FunctionSet, lf: &Arc<dyn LinkingFunction>, mut gene_nbr:usize, mut head_length: usize, mut args_nbr:usize, max_delay: usize) -> Self{
         if gene_nbr < 1 { gene_nbr = 1}
         if head_length < 1 { head_length = 1}
         if args_nbr < 1 { args_nbr = 1}
 
-        let gl = 2* head_length + 1;
-        let mut codons: Vec<Codon> = Vec::with_capacity(gene_nbr *gl);
-        Self::initialize_codons(&mut rng, &mut codons, gene_nbr, head_length, args_nbr, max_delay);
-        Chromosome { codons, head_size: head_length, nbr_of_genes: gene_nbr, fitness:Cell::new(0.0) }
+        let tail_length = head_length * (usize::from(fs.max_arity()) - 1) + 1;
+        let layout = Layout { genes: gene_nbr, head: head_length, tail: tail_length, args: args_nbr, max_delay };
+        let codons = Self::initialize_codons(rng, fs, &layout);
+        let weights = if lf.weighted() {(0..gene_nbr).map(|_| rng.gen_range(-1.0..1.0)).collect()} else {Vec::new()};
+        let nc = fs.constants();
+        let (dc, constants) = if nc > 0 {
+            ((0..gene_nbr * tail_length).map(|_| rng.gen::<usize>() % nc).collect(),
+             (0..gene_nbr * nc).map(|_| fs.random_constant(rng)).collect())
+        } else {(Vec::new(), Vec::new())};
+        Chromosome { codons, head_size: head_length, tail_size: tail_length, nbr_of_genes: gene_nbr, linker: lf.clone(),
+            homeotic_head: 0, homeotic_tail: 0, homeotic_nbr: 0, homeotic: Vec::new(), weights, dc, constants, fitness: 0.0 }
+    }
+    // replaces homeotic genes by `nbr` random ones (0 - none) with the given head length;
+    // weights of a weighted linking function are drawn again for the new outputs
+    pub fn set_homeotic<R: Rng + ?Sized>(&mut self, rng: &mut R, fs: &FunctionSet, nbr: usize, head_length: usize) {
+        let head_length = head_length.max(1);
+        self.homeotic_nbr = nbr;
+        self.homeotic_head = if nbr > 0 {head_length} else {0};
+        self.homeotic_tail = if nbr > 0 {head_length * (usize::from(fs.max_arity()) - 1) + 1} else {0};
+        self.homeotic = Vec::with_capacity(nbr * self.homeotic_length());
+        for _i in 0..nbr {
+            self.homeotic.push(fs.random_non_terminal(rng));
+            for _j in 1..self.homeotic_head {
+                let c = self.random_homeotic_codon(rng, fs);
+                self.homeotic.push(c);
+            }
+            for _j in 0..self.homeotic_tail {
+                self.homeotic.push(Codon::Terminal(Terminal::new(rng.gen::<usize>() % self.nbr_of_genes)));
+            }
+        }
+        if self.linker.weighted() {
+            let outputs = if nbr > 0 {nbr} else {self.nbr_of_genes};
+            self.weights = (0..outputs).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        }
+    }
+    fn homeotic_length(&self) -> usize {
+        self.homeotic_head + self.homeotic_tail
     }
-    fn initialize_codons(mut rng: &mut ThreadRng, arr: &mut Vec<Codon>, gn:usize, hl:usize, na:usize, md: usize){
-        let gl = 2*hl+1;
+    // head codon of a homeotic gene: a function or an ADF reference
+    fn random_homeotic_codon<R: Rng + ?Sized>(&self, rng: &mut R, fs: &FunctionSet) -> Codon {
+        if rng.gen::<usize>() % 20 < 4 {
+            Codon::Terminal(Terminal::new(rng.gen::<usize>() % self.nbr_of_genes))
+        } else {
+            fs.random_non_terminal(rng)
+        }
+    }
+    // weights belong to genes, not to homeotic genes
+    fn gene_weights(&self) -> bool {
+        self.homeotic.is_empty()
+    }
+    // input terminal or, with constants, `?` as one more input
+    fn random_terminal<R: Rng + ?Sized>(rng: &mut R, fs: &FunctionSet, na: usize) -> Codon {
+        if fs.constants() > 0 && rng.gen::<usize>() % (na + 1) == na {
+            Codon::Constant
+        } else {
+            Codon::Terminal(Terminal::new(rng.gen::<usize>() % na))
+        }
+    }
+    fn initialize_codons<R: Rng + ?Sized>(rng: &mut R, fs: &FunctionSet, layout: &Layout) -> Vec<Codon>{
+        let Layout { genes: gn, head: hl, tail: tl, args: na, max_delay: md } = *layout;
+        let gl = hl+tl;
+        let mut arr: Vec<Codon> = Vec::with_capacity(gn *gl);
         for i in 0..gn{
             let start = i*gl;
             // first codon in gene must be non-terminal
-            arr.push(Self::create_non_terminal(rng));
+            arr.push(fs.random_non_terminal(rng));
             for _j in start+1..start+hl{
-                Self::push_head_codon(&mut rng, arr, na, md);
+                Self::push_head_codon(rng, fs, &mut arr, na, md);
             }
             for _j in start+hl..start+gl{
                 let r = if md > 0 {rng.gen::<usize>() % 4} else {rng.gen::<usize>() % 3};
                 if r < 3 {
-                    arr.push(Codon::Terminal(Terminal::new(rng.gen::<usize>() % na)))
+                    arr.push(Self::random_terminal(rng, fs, na))
                 } else {
                     arr.push(Codon::Feedback(Feedback::new(rng.gen::<usize>() % md)))
                 }
 
             }
         }
+        arr
     }
-    fn push_head_codon(rng: &mut ThreadRng, arr: &mut Vec<Codon>, na:usize, md: usize) {
+    fn push_head_codon<R: Rng + ?Sized>(rng: &mut R, fs: &FunctionSet, arr: &mut Vec<Codon>, na:usize, md: usize) {
         let r = rng.gen::<usize>() % 20;
         let k = if md > 0 {3} else {4};
         if r < k {
-            arr.push(Codon::Terminal(Terminal::new(rng.gen::<usize>() % na)));
+            arr.push(Self::random_terminal(rng, fs, na));
         } else if r < 4{
             arr.push(Codon::Feedback(Feedback::new(rng.gen::<usize>() % md)));
         }
         else {
-            let c = Self::create_non_terminal(rng);
+            let c = fs.random_non_terminal(rng);
             arr.push(c);
         }
     }
-    fn create_non_terminal(rng: &mut ThreadRng) -> Codon {
-        let i = rng.gen::<usize>() % (FN_NUM + SFN_NUM);
-        if i < 4 { Codon::Function(Function::new(&(FREGISTRY[i]))) }
-        else { Codon::StateFunction(StateFunction::new(&(SFREGISTRY[i-4]))) }
-    }
 
     pub fn copy_to_new_generation(&self) ->Chromosome{
         let len = self.codons.len();
@@ -75,41 +292,187 @@ impl Chromosome{
         for i in 0.. self.codons.len(){
             codons.push(self.codons[i].clone());
         }
-        Chromosome { head_size: self.head_size, nbr_of_genes: self.nbr_of_genes, codons, fitness:Cell::new(0.0) }
+        Chromosome { head_size: self.head_size, tail_size: self.tail_size, nbr_of_genes: self.nbr_of_genes,
+            linker: self.linker.clone(), homeotic_head: self.homeotic_head, homeotic_tail: self.homeotic_tail,
+            homeotic_nbr: self.homeotic_nbr, homeotic: self.homeotic.clone(), codons, weights: self.weights.clone(), dc: self.dc.clone(),
+            constants: self.constants.clone(), fitness: 0.0 }
+    }
+
+    pub fn gene_length(&self) -> usize {
+        self.head_size + self.tail_size
     }
 
     // helpers
     pub fn k_string(&self) ->String{
-        let len = self.head_size*2+1;
+        let len = self.gene_length();
         let mut ret = String::new();
-        ret.push_str (LF.symbol);
+        ret.push_str (&self.linker.symbol());
+        if !self.weights.is_empty() {
+            let w: Vec<String> = self.weights.iter().map(|w| w.to_string()).collect();
+            ret.push_str(&format!("({})", w.join(",")));
+        }
         for i in 0..self.nbr_of_genes {
             let start = i * len;
             for j in start..start + len {
                 ret.push_str(" |");
-                let c = &self.codons[j];
-                match c {
-                    Codon::Terminal(ref t) => ret.push_str(&t.i.to_string()),
-                    Codon::Feedback(ref f) => ret.push_str(&*format!("z({})", f.i.to_string())),
-                    _ => ret.push_str(&c.get_symbol())
-                }
+                ret.push_str(&self.codons[j].token());
                 ret.push('|');
             }
+            if !self.dc.is_empty() {
+                let dc: Vec<String> = self.gene_dc(i).iter().map(|d| d.to_string()).collect();
+                let c: Vec<String> = self.gene_constants(i).iter().map(|c| c.to_string()).collect();
+                ret.push_str(&format!(" Dc({}) C({})", dc.join(","), c.join(",")));
+            }
             if i < self.nbr_of_genes-1 {ret.push_str(" //");}
         }
+        // homeotic genes follow the ADFs
+        let hl = self.homeotic_length();
+        for i in 0..self.homeotic_nbr {
+            ret.push_str(if i == 0 {" ##"} else {" //"});
+            for c in &self.homeotic[i * hl..(i + 1) * hl] {
+                ret.push_str(&format!(" |{}|", c.token()));
+            }
+        }
         ret
     }
 
+    // parses a k-string back; functions are resolved against fs, the function set the chromosome
+    // was evolved with (its maximum arity gives head lengths); fitness is zero; None if the string
+    // is malformed or inconsistent
+    pub fn from_k_string(k: &str, fs: &FunctionSet) -> Option<Chromosome> {
+        fn list<T: std::str::FromStr>(s: &str) -> Option<Vec<T>> {
+            if s.is_empty() { return Some(Vec::new()) }
+            s.split(',').map(|x| x.parse().ok()).collect()
+        }
+        fn codons(s: &str, fs: &FunctionSet) -> Option<Vec<Codon>> {
+            s.split_whitespace().map(|t| fs.codon(t.strip_prefix('|')?.strip_suffix('|')?)).collect()
+        }
+        // genes of equal length and the head length of them
+        fn genes(genes: &[Vec<Codon>], n: usize) -> Option<usize> {
+            let len = genes[0].len();
+            if len < 2 || !(len - 1).is_multiple_of(n) || genes.iter().any(|g| g.len() != len) { return None }
+            Some((len - 1) / n)
+        }
+        let n = usize::from(fs.max_arity());
+        let split = k.find(" |")?;
+        let (symbol, weights) = match k[..split].split_once('(') {
+            Some((symbol, w)) => (symbol, list(w.strip_suffix(')')?)?),
+            None => (&k[..split], Vec::new()),
+        };
+        let linker = crate::linking_function::from_symbol(symbol)?;
+        let (adfs, homeotic) = match k[split..].split_once(" ##") {
+            Some((a, h)) => (a, h),
+            None => (&k[split..], ""),
+        };
+        let (mut gene_codons, mut dc, mut constants) = (Vec::new(), Vec::new(), Vec::new());
+        for gene in adfs.split(" //") {
+            let gene = match gene.split_once(" Dc(") {
+                Some((gene, rnc)) => {
+                    let (d, c) = rnc.strip_suffix(')')?.split_once(") C(")?;
+                    dc.extend(list::<usize>(d)?);
+                    constants.push(list::<f32>(c)?);
+                    gene
+                }
+                None => gene,
+            };
+            gene_codons.push(codons(gene, fs)?);
+        }
+        let head_size = genes(&gene_codons, n)?;
+        let homeotic_codons: Vec<Vec<Codon>> = if homeotic.is_empty() {Vec::new()} else {
+            homeotic.split(" //").map(|g| codons(g, fs)).collect::<Option<_>>()?
+        };
+        let homeotic_head = if homeotic_codons.is_empty() {0} else {genes(&homeotic_codons, n)?};
+        // every gene has constants or none does, all of the same number
+        if !constants.is_empty() && (constants.len() != gene_codons.len() || constants.iter().any(|c| c.len() != constants[0].len())) {
+            return None;
+        }
+        let c = Chromosome {
+            head_size,
+            tail_size: gene_codons[0].len() - head_size,
+            nbr_of_genes: gene_codons.len(),
+            linker,
+            homeotic_head,
+            homeotic_tail: homeotic_codons.first().map_or(0, |g| g.len() - homeotic_head),
+            homeotic_nbr: homeotic_codons.len(),
+            homeotic: homeotic_codons.into_iter().flatten().collect(),
+            codons: gene_codons.into_iter().flatten().collect(),
+            weights,
+            dc,
+            constants: constants.into_iter().flatten().collect(),
+            fitness: 0.0,
+        };
+        if c.is_consistent() {Some(c)} else {None}
+    }
+
+    // sizes of all parts agree, roots are non-terminals and tails terminals, Dc indexes and
+    // references of homeotic genes are in range; inputs and delays are not checked
+    fn is_consistent(&self) -> bool {
+        let gl = self.gene_length();
+        let hl = self.homeotic_length();
+        let gn = self.nbr_of_genes;
+        let nc = self.constants.len().checked_div(gn).unwrap_or(0);
+        let outputs = if self.homeotic_nbr > 0 {self.homeotic_nbr} else {gn};
+        let genes_ok = gn > 0 && self.head_size > 0 && self.codons.len() == gn * gl && (0..gn).all(|g| {
+            let gene = &self.codons[g * gl..(g + 1) * gl];
+            !gene[0].is_terminal() && gene[self.head_size..].iter().all(|c| c.is_terminal())
+        });
+        let rnc_ok = if self.dc.is_empty() {self.constants.is_empty()} else {
+            nc > 0 && self.constants.len() == gn * nc && self.dc.len() == gn * self.tail_size && self.dc.iter().all(|d| *d < nc)
+        };
+        let homeotic_ok = self.homeotic.len() == self.homeotic_nbr * hl && (0..self.homeotic_nbr).all(|h| {
+            let gene = &self.homeotic[h * hl..(h + 1) * hl];
+            self.homeotic_head > 0 && !gene[0].is_terminal()
+                && gene[self.homeotic_head..].iter().all(|c| c.is_terminal())
+                && gene.iter().all(|c| match c {
+                    Codon::Terminal(ref t) => t.i < gn,
+                    Codon::Feedback(_) | Codon::Constant => false,
+                    _ => true,
+                })
+        });
+        let weights_ok = self.weights.len() == if self.linker.weighted() {outputs} else {0};
+        genes_ok && rnc_ok && homeotic_ok && weights_ok
+    }
+
+    // symbols of user functions among the codons, in order of first use
+    fn user_functions(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = Vec::new();
+        for c in self.codons.iter().chain(&self.homeotic) {
+            if let Codon::Function(ref f) = c {
+                if !f.fd.builtin && !symbols.contains(&f.fd.symbol) { symbols.push(f.fd.symbol.clone()) }
+            }
+        }
+        symbols
+    }
+
+    fn gene_dc(&self, g: usize) -> &[usize] {
+        &self.dc[g * self.tail_size..(g + 1) * self.tail_size]
+    }
+    fn gene_constants(&self, g: usize) -> &[f32] {
+        let nc = self.constants.len() / self.nbr_of_genes;
+        &self.constants[g * nc..(g + 1) * nc]
+    }
+    // values of the `?` codons of every gene in order of position
+    fn constant_values(&self) -> Vec<Vec<f32>> {
+        if self.dc.is_empty() { return Vec::new() }
+        let glen = self.gene_length();
+        (0..self.nbr_of_genes).map(|g| {
+            let (dc, constants) = (self.gene_dc(g), self.gene_constants(g));
+            let cnt = self.codons[g * glen..(g + 1) * glen].iter().filter(|c| matches!(c, Codon::Constant)).count();
+            // the expressed part holds at most tail_size terminals, so later `?` are never read
+            (0..cnt.min(dc.len())).map(|k| constants[dc[k]]).collect()
+        }).collect()
+    }
+
     // genetic operations
-    pub fn mutation(&self, rng: &mut ThreadRng, args_nbr:usize, md:usize, codon_mutation_probability: f32) -> Chromosome{
-        let glen = self.head_size*2+1;
+    pub fn mutation<R: Rng + ?Sized>(&self, rng: &mut R, fs: &FunctionSet, args_nbr:usize, md:usize, codon_mutation_probability: f32) -> Chromosome{
+        let glen = self.gene_length();
         // codons for mutated chromosome
         let mut codons: Vec<Codon> = Vec::with_capacity(self.codons.len());
         for i in 0..self.nbr_of_genes {
             let start = i * glen;
             //mutate first head position (must be non-terminal)
             if rng.gen_range(0.0..1.0) < codon_mutation_probability{
-                codons.push(Self::create_non_terminal(rng));
+                codons.push(fs.random_non_terminal(rng));
             }
             else{
                 codons.push(self.codons[start].clone());
@@ -120,12 +483,12 @@ impl Chromosome{
                     let r = rng.gen::<usize>() % 20;
                     let k = if md > 0 {3} else {4};
                     if r < k {
-                        codons.push(Codon::Terminal(Terminal::new(rng.gen::<usize>() % args_nbr)));
+                        codons.push(Self::random_terminal(rng, fs, args_nbr));
                     }else if r < 4 {
                         codons.push(Codon::Feedback(Feedback::new(rng.gen::<usize>() % md)));
                     }
                     else {
-                        codons.push(Self::create_non_terminal(rng));
+                        codons.push(fs.random_non_terminal(rng));
                     }
                 }
                 else{
@@ -138,7 +501,7 @@ impl Chromosome{
                     let r = rng.gen::<usize>() % 4;
                     let k = if md > 0{3} else {4};
                     if r < k {
-                        codons.push(Codon::Terminal(Terminal::new(rng.gen::<usize>() % args_nbr)));
+                        codons.push(Self::random_terminal(rng, fs, args_nbr));
                     } else {
                         codons.push(Codon::Feedback(Feedback::new(rng.gen::<usize>() % md)));
                     }
@@ -148,29 +511,92 @@ impl Chromosome{
                 }
             }
         }
-        Chromosome { codons, head_size:self.head_size, nbr_of_genes:self.nbr_of_genes, fitness: Cell::new(0.0) }
+        // mutate weights of genes
+        let mut weights = self.weights.clone();
+        for w in weights.iter_mut() {
+            if rng.gen_range(0.0..1.0) < codon_mutation_probability{
+                *w = rng.gen_range(-1.0..1.0);
+            }
+        }
+        // mutate the Dc domain
+        let mut dc = self.dc.clone();
+        let nc = self.constants.len() / self.nbr_of_genes;
+        for d in dc.iter_mut() {
+            if rng.gen_range(0.0..1.0) < codon_mutation_probability{
+                *d = rng.gen::<usize>() % nc;
+            }
+        }
+        // mutate homeotic genes
+        let hl = self.homeotic_length();
+        let mut homeotic: Vec<Codon> = Vec::with_capacity(self.homeotic.len());
+        for (j, c) in self.homeotic.iter().enumerate() {
+            if rng.gen_range(0.0..1.0) < codon_mutation_probability {
+                homeotic.push(match j % hl {
+                    0 => fs.random_non_terminal(rng),
+                    k if k < self.homeotic_head => self.random_homeotic_codon(rng, fs),
+                    _ => Codon::Terminal(Terminal::new(rng.gen::<usize>() % self.nbr_of_genes)),
+                });
+            } else {
+                homeotic.push(c.clone());
+            }
+        }
+        Chromosome { codons, head_size:self.head_size, tail_size:self.tail_size, nbr_of_genes:self.nbr_of_genes,
+            linker: self.linker.clone(), homeotic_head: self.homeotic_head, homeotic_tail: self.homeotic_tail,
+            homeotic_nbr: self.homeotic_nbr, homeotic, weights, dc, constants: self.constants.clone(), fitness: 0.0 }
+    }
+    // every constant is replaced by a random one with the given probability
+    pub fn constant_mutation<R: Rng + ?Sized>(&mut self, rng: &mut R, fs: &FunctionSet, mutation_probability: f32) {
+        if mutation_probability <= 0.0 {return;}
+        for c in self.constants.iter_mut() {
+            if rng.gen_range(0.0..1.0) < mutation_probability {
+                *c = fs.random_constant(rng);
+            }
+        }
+    }
+    // Dc transposition: a sequence of 1-3 indexes of the Dc domain of a gene is inserted elsewhere
+    // in the same domain; the rest is shifted and its last indexes are lost
+    pub fn dc_transposition<R: Rng + ?Sized>(&mut self, rng: &mut R, transposition_probability: f32) {
+        if transposition_probability <= 0.0 || self.dc.is_empty() {return;}
+        if rng.gen_range(0.0..1.0) >= transposition_probability {return;}
+        let tl = self.tail_size;
+        let start = tl * (rng.gen::<usize>() % self.nbr_of_genes);
+        let ip = rng.gen::<usize>() % tl;
+        let source = rng.gen::<usize>() % tl;
+        let l = (1 + rng.gen::<usize>() % 3).min(tl - ip).min(tl - source);
+        let dc = &mut self.dc[start..start + tl];
+        let transposon = dc[source..source + l].to_vec();
+        dc[ip..].rotate_right(l);
+        dc[ip..ip + l].copy_from_slice(&transposon);
     }
-    pub fn root_transposition(&mut self, rng: &mut ThreadRng, transposition_probability : f32) {
+    // genes and homeotic genes are targets alike; a transposon comes from the same kind of gene
+    pub fn root_transposition<R: Rng + ?Sized>(&mut self, rng: &mut R, transposition_probability : f32) {
         let test = rng.gen_range(0.0..1.0);
         if test >= transposition_probability {return;}
-        let glen = self.head_size*2+1;
         // 1) select target gene
-        let target_gene = rng.gen::<usize>() % self.nbr_of_genes;
+        let target_gene = rng.gen::<usize>() % (self.nbr_of_genes + self.homeotic_nbr);
+        let domain = self.domain(target_gene);
+        Self::transpose_root(domain, rng);
+    }
+    fn transpose_root<R: Rng + ?Sized>(domain: Domain, rng: &mut R) {
+        let Domain { codons, target_gene, genes, head_size, gene_length: glen } = domain;
         let mut ip = glen * target_gene;
         // 2) select source gene
-        let source_gene = rng.gen::<usize>() % self.nbr_of_genes;
+        let source_gene = rng.gen::<usize>() % genes;
         let ss = glen * source_gene; // source gene head start
         // 3) select starting point of transposon (within head of source gene)
-        let mut starting_point = rng.gen::<usize>() % self.head_size + ss;
+        let mut starting_point = rng.gen::<usize>() % head_size + ss;
         //  3.1) if starting point is a root of target gene - move starting point forward, to next codon
         if starting_point == ip {starting_point += 1}
-        let se = ss + self.head_size; // source gene tail start
+        let se = ss + head_size; // source gene tail start
+        // a head of one codon holds the root only
+        if starting_point == se {return;}
         // try to move starting_point to position of first occurrence of a non-terminal in a given transposon
         // because after transposition modified gene cannot start with a terminal
         loop{
-            match self.codons[starting_point]{
+            match codons[starting_point]{
                 Codon::Terminal(ref _t) => starting_point += 1,
                 Codon::Feedback(ref _f) => starting_point += 1,
+                Codon::Constant => starting_point += 1,
                 _ => break,
             }
             // only terminals found -> no transposition
@@ -187,97 +613,637 @@ impl Chromosome{
         // 5) clone transposon starting with a root of target gene
         // because of restriction applied to length of transposon, we can clone codons directly, without using temporary buffer
         for i in starting_point..end_point+1{
-            self.codons[ip] = self.codons[i].clone();
+            codons[ip] = codons[i].clone();
             ip += 1;
         }
     }
-    pub fn two_points_crossover(chrs: &mut Vec<Chromosome>, i1: usize, i2: usize, rng: &mut ThreadRng) {
-        let len = chrs[0].codons.len();
+    // genes (g < nbr_of_genes) or homeotic genes holding gene g
+    fn domain(&mut self, g: usize) -> Domain<'_> {
+        if g < self.nbr_of_genes {
+            Domain { target_gene: g, genes: self.nbr_of_genes, head_size: self.head_size, gene_length: self.gene_length(),
+                codons: &mut self.codons }
+        } else {
+            Domain { target_gene: g - self.nbr_of_genes, genes: self.homeotic_nbr, head_size: self.homeotic_head,
+                gene_length: self.homeotic_length(), codons: &mut self.homeotic }
+        }
+    }
+    // IS transposition: a sequence of 1-3 codons from anywhere in the genes (homeotic genes) is inserted
+    // into the head of a gene (homeotic gene) after its root; the rest of the head is shifted and its
+    // last codons are lost
+    pub fn is_transposition<R: Rng + ?Sized>(&mut self, rng: &mut R, transposition_probability: f32) {
+        if transposition_probability <= 0.0 || self.head_size.max(self.homeotic_head) < 2 {return;}
+        if rng.gen_range(0.0..1.0) >= transposition_probability {return;}
+        let Domain { codons, target_gene, head_size, gene_length, .. } =
+            self.domain(rng.gen::<usize>() % (self.nbr_of_genes + self.homeotic_nbr));
+        if head_size < 2 {return;}
+        let start = gene_length * target_gene;
+        let ip = 1 + rng.gen::<usize>() % (head_size - 1);
+        let source = rng.gen::<usize>() % codons.len();
+        let l = (1 + rng.gen::<usize>() % 3).min(head_size - ip).min(codons.len() - source);
+        // the transposon may overlap the target head, so it is copied first
+        let transposon = codons[source..source + l].to_vec();
+        let head = &mut codons[start..start + head_size];
+        for i in (ip + l..head.len()).rev() {
+            head[i] = head[i - l].clone();
+        }
+        for (i, c) in transposon.into_iter().enumerate() {
+            head[ip + i] = c;
+        }
+    }
+    // gene transposition: a gene (other than the first) is moved to the beginning of the chromosome
+    pub fn gene_transposition<R: Rng + ?Sized>(&mut self, rng: &mut R, transposition_probability: f32) {
+        if transposition_probability <= 0.0 || self.nbr_of_genes < 2 {return;}
+        if rng.gen_range(0.0..1.0) >= transposition_probability {return;}
+        let glen = self.gene_length();
+        let g = 1 + rng.gen::<usize>() % (self.nbr_of_genes - 1);
+        self.codons[..(g + 1) * glen].rotate_right(glen);
+        if !self.weights.is_empty() && self.gene_weights() {
+            self.weights[..g + 1].rotate_right(1);
+        }
+        if !self.dc.is_empty() {
+            let nc = self.constants.len() / self.nbr_of_genes;
+            self.dc[..(g + 1) * self.tail_size].rotate_right(self.tail_size);
+            self.constants[..(g + 1) * nc].rotate_right(nc);
+        }
+    }
+    // inversion: a sequence within the head of a gene or homeotic gene is reversed; the root stays in place
+    pub fn inversion<R: Rng + ?Sized>(&mut self, rng: &mut R, inversion_probability: f32) {
+        if inversion_probability <= 0.0 || self.head_size.max(self.homeotic_head) < 3 {return;}
+        if rng.gen_range(0.0..1.0) >= inversion_probability {return;}
+        let Domain { codons, target_gene, head_size, gene_length, .. } =
+            self.domain(rng.gen::<usize>() % (self.nbr_of_genes + self.homeotic_nbr));
+        if head_size < 3 {return;}
+        let start = gene_length * target_gene;
+        let a = 1 + rng.gen::<usize>() % (head_size - 1);
+        let b = 1 + rng.gen::<usize>() % (head_size - 1);
+        let (lo, hi) = if a < b {(a, b)} else {(b, a)};
+        codons[start + lo..start + hi + 1].reverse();
+    }
+
+    // i1 < i2
+    fn pair(chrs: &mut [Chromosome], i1: usize, i2: usize) -> (&mut Chromosome, &mut Chromosome) {
+        let (a, b) = chrs.split_at_mut(i2);
+        (&mut a[i1], &mut b[0])
+    }
+    // the Dc domain and constants of gene g
+    fn swap_constants(p1: &mut Chromosome, p2: &mut Chromosome, g: usize) {
+        if p1.dc.is_empty() {return;}
+        let (tl, nc) = (p1.tail_size, p1.constants.len() / p1.nbr_of_genes);
+        p1.dc[g * tl..(g + 1) * tl].swap_with_slice(&mut p2.dc[g * tl..(g + 1) * tl]);
+        p1.constants[g * nc..(g + 1) * nc].swap_with_slice(&mut p2.constants[g * nc..(g + 1) * nc]);
+    }
+    // codons start..end of the genes followed by the homeotic genes
+    fn swap_codons(p1: &mut Chromosome, p2: &mut Chromosome, start: usize, end: usize) {
+        let len = p1.codons.len();
+        for i in start..end {
+            if i < len {
+                std::mem::swap(&mut p1.codons[i], &mut p2.codons[i]);
+            } else {
+                std::mem::swap(&mut p1.homeotic[i - len], &mut p2.homeotic[i - len]);
+            }
+        }
+    }
+    // position of the root of the gene or homeotic gene owning weight i among the codons of swap_codons
+    fn weight_root(&self, i: usize) -> usize {
+        if self.gene_weights() {i * self.gene_length()} else {self.codons.len() + i * self.homeotic_length()}
+    }
+    // one-point crossover: codons (homeotic genes following the genes) after a random point are swapped
+    pub fn one_point_crossover<R: Rng + ?Sized>(chrs: &mut [Chromosome], i1: usize, i2: usize, rng: &mut R) {
+        let len = chrs[i1].codons.len() + chrs[i1].homeotic.len();
+        if len < 2 || !chrs[i1].same_structure(&chrs[i2]) {return;}
+        let point = 1 + rng.gen::<usize>() % (len - 1);
+        let (p1, p2) = Self::pair(chrs, i1, i2);
+        Self::swap_codons(p1, p2, point, len);
+        // weights follow roots of their genes
+        let glen = p1.gene_length();
+        for i in 0..p1.weights.len().min(p2.weights.len()) {
+            if p1.weight_root(i) >= point {
+                std::mem::swap(&mut p1.weights[i], &mut p2.weights[i]);
+            }
+        }
+        // the Dc domain follows the tail of its gene
+        for g in point / glen..p1.nbr_of_genes {
+            Self::swap_constants(p1, p2, g);
+        }
+    }
+    // gene recombination: a whole gene or homeotic gene (the same one in both chromosomes) is swapped
+    pub fn gene_recombination<R: Rng + ?Sized>(chrs: &mut [Chromosome], i1: usize, i2: usize, rng: &mut R) {
+        if !chrs[i1].same_structure(&chrs[i2]) {return;}
+        let glen = chrs[i1].gene_length();
+        let gn = chrs[i1].nbr_of_genes;
+        let g = rng.gen::<usize>() % (gn + chrs[i1].homeotic_nbr);
+        let (p1, p2) = Self::pair(chrs, i1, i2);
+        if g >= gn {
+            let (h, hl) = (g - gn, p1.homeotic_length());
+            p1.homeotic[h * hl..(h + 1) * hl].swap_with_slice(&mut p2.homeotic[h * hl..(h + 1) * hl]);
+            if h < p1.weights.len() {
+                std::mem::swap(&mut p1.weights[h], &mut p2.weights[h]);
+            }
+            return;
+        }
+        for i in g * glen..(g + 1) * glen {
+            std::mem::swap(&mut p1.codons[i], &mut p2.codons[i]);
+        }
+        if p1.gene_weights() && g < p1.weights.len().min(p2.weights.len()) {
+            std::mem::swap(&mut p1.weights[g], &mut p2.weights[g]);
+        }
+        Self::swap_constants(p1, p2, g);
+    }
+    pub fn two_points_crossover<R: Rng + ?Sized>(chrs: &mut [Chromosome], i1: usize, i2: usize, rng: &mut R) {
+        Self::two_points_crossover_with(chrs, i1, i2, CrossoverMode::Free, rng)
+    }
+    // chromosomes of different structure (gene length or number of genes) are not crossed
+    pub fn two_points_crossover_with<R: Rng + ?Sized>(chrs: &mut [Chromosome], i1: usize, i2: usize, mode: CrossoverMode, rng: &mut R) {
+        if !chrs[i1].same_structure(&chrs[i2]) {return;}
+        let glen = chrs[i1].gene_length();
+        let gn = chrs[i1].nbr_of_genes;
+        let (hl, hn) = (chrs[i1].homeotic_length(), chrs[i1].homeotic_nbr);
+        // start of gene g, homeotic genes following the genes
+        let gene_start = |g: usize| if g <= gn {g * glen} else {gn * glen + (g - gn) * hl};
+        // swapped range, end excluded
+        let (start, end) = match mode {
+            CrossoverMode::Free => {
+                let (p1, p2) = Self::two_points(glen * gn + hl * hn, rng);
+                (p1, p2 + 1)
+            }
+            CrossoverMode::Genes => {
+                // n genes have n+1 boundaries
+                let (b1, b2) = Self::two_points(gn + hn + 1, rng);
+                (gene_start(b1), gene_start(b2))
+            }
+            CrossoverMode::InGene => {
+                let g = rng.gen::<usize>() % (gn + hn);
+                let (p1, p2) = Self::two_points(if g < gn {glen} else {hl}, rng);
+                (gene_start(g) + p1, gene_start(g) + p2 + 1)
+            }
+        };
+        let (p1, p2) = Self::pair(chrs, i1, i2);
+        Self::swap_codons(p1, p2, start, end);
+        // weights follow roots of their genes
+        for i in 0..p1.weights.len().min(p2.weights.len()) {
+            if (start..end).contains(&p1.weight_root(i)) {
+                std::mem::swap(&mut p1.weights[i], &mut p2.weights[i]);
+            }
+        }
+        // the Dc domain follows the tail of its gene
+        for g in 0..p1.nbr_of_genes {
+            if start < (g + 1) * glen && (g + 1) * glen <= end {
+                Self::swap_constants(p1, p2, g);
+            }
+        }
+    }
+    // two different points in 0..len (len > 1), ordered
+    fn two_points<R: Rng + ?Sized>(len: usize, rng: &mut R) -> (usize, usize) {
         let point1 = rng.gen::<usize>() % len;
         let mut point2:usize;
         loop{
             point2 = rng.gen::<usize>() % len;
             if point1 != point2 {break}
         }
-        let (start,end) = if point1 < point2 {(point1,point2)} else {(point2,point1)};
-        // dbg!(start);
-        // dbg!(end);
-        let (a, b) = chrs.split_at_mut(i2);
-        let p1 = &mut a[i1];
-        let p2 = &mut b[0];
-        for i in start..end+1{
-            let (x, y) = (p2.codons[i].clone(), p1.codons[i].clone());
-            p1.codons[i] = x;
-            p2.codons[i] = y;
-        }
+        if point1 < point2 {(point1,point2)} else {(point2,point1)}
+    }
+    fn same_structure(&self, other: &Chromosome) -> bool {
+        self.head_size == other.head_size && self.tail_size == other.tail_size && self.nbr_of_genes == other.nbr_of_genes
+            && self.dc.len() == other.dc.len() && self.constants.len() == other.constants.len()
+            && self.homeotic_head == other.homeotic_head && self.homeotic_nbr == other.homeotic_nbr
     }
 
     // translation/execution
-    pub fn translate<'a>(&'a self) -> Box<dyn Fn(&Vec<f32>, &DelayLine) -> f32 + 'a>{
-        let glen = 2*self.head_size+1;
-        let cnt = self.nbr_of_genes;
-        let mut idx = 0;
-        // first pass
-        for _i in 0..cnt{
-            self.first_pass(idx);
-            idx += glen;
-        }
-        let mut results: Vec<Box<dyn Fn(&Vec<f32>,&DelayLine) -> f32>> = Vec::with_capacity(cnt);
-        // second pass
-        idx = 0;
-        for _i in 0..cnt{
-            results.push(self.second_pass(idx));
-            idx += glen;
-        }
-        Box::new(move |args, delay_line| {
-            let mut v :Vec<f32> = Vec::with_capacity(cnt);
-            for i in 0..cnt{
-                v.push((results[i])(args,delay_line))
-            }
-            LF.evaluate(v)
-        })
+    pub fn translate(&self) -> Program<'_>{
+        let program = Program::with_constants(&self.codons, self.gene_length(), self.nbr_of_genes, &*self.linker,
+                                              &self.weights, &self.constant_values());
+        if self.homeotic.is_empty() {program} else {program.with_homeotic(&self.homeotic, self.homeotic_length(), self.homeotic_nbr)}
     }
-    pub fn pass<'a>(&'a self, max_delay:usize, evaluator: &'a impl FitnessEvaluator) -> f32{
-        let func = self.translate();
-        let f = evaluator.evaluate(max_delay, func);
-        self.fitness.set(f);
-        f
+    // expressed genes and linking function, to be rendered as text, Graphviz or Rust source
+    pub fn expression(&self) -> Expression<'_> {
+        let expression = Expression::new(&self.codons, self.gene_length(), self.nbr_of_genes, &*self.linker,
+                                         &self.weights, &self.constant_values());
+        if self.homeotic.is_empty() {expression} else {expression.with_homeotic(&self.homeotic, self.homeotic_length(), self.homeotic_nbr)}
+    }
+    // standalone Rust struct `name` with new() and step(&[f32]) -> f32, which gives the same outputs
+    // as translate() evaluated with a delay line of max_delay outputs; None with user functions
+    pub fn to_rust(&self, name: &str, max_delay: usize) -> Option<String> {
+        codegen::module(&self.expression(), name, max_delay)
     }
-    pub fn equity<'a>(&'a self, max_delay: usize,  train:bool, evaluator: &'a impl FitnessEvaluator) -> Vec<f32>{
-        let func = self.translate();
-        let f = evaluator.equity(max_delay, train,func);
+    pub fn pass(&mut self, max_delay:usize, evaluator: &(impl FitnessEvaluator + ?Sized)) -> f32{
+        let mut program = self.translate();
+        let f = evaluator.evaluate(max_delay, &mut program);
+        self.fitness = f;
         f
     }
-    fn first_pass(&self, p:usize) {
-        let mut pos = p;
-        let mut first_arg_position:usize = p+1;
-        loop {
-            let current = &self.codons[pos];
-            current.set_first_arg_position(first_arg_position);
-            first_arg_position += usize::from(current.get_arity());
-            pos += 1;
-            if pos == first_arg_position {
-                break;
+    pub fn objectives(&self, max_delay:usize, evaluator: &(impl MultiObjectiveEvaluator + ?Sized)) -> Vec<f32>{
+        let mut program = self.translate();
+        evaluator.objectives(max_delay, &mut program)
+    }
+    pub fn equity<'a>(&'a self, max_delay: usize,  train:bool, evaluator: &'a (impl FitnessEvaluator + ?Sized)) -> Vec<f32>{
+        let mut program = self.translate();
+        evaluator.equity(max_delay, train, &mut program)
+    }
+    pub fn outputs<'a>(&'a self, max_delay: usize,  train:bool, evaluator: &'a (impl FitnessEvaluator + ?Sized)) -> Vec<f32>{
+        let mut program = self.translate();
+        evaluator.outputs(max_delay, train, &mut program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use super::*;
+
+    // the expressed part of every gene fits into the gene, roots are non-terminals, tails hold terminals only
+    fn is_valid(c: &Chromosome) -> bool {
+        let gl = c.gene_length();
+        (0..c.nbr_of_genes).all(|g| {
+            let gene = &c.codons[g * gl..(g + 1) * gl];
+            let mut end = 1;
+            let mut pos = 0;
+            while pos < end {
+                end += usize::from(gene[pos].get_arity());
+                pos += 1;
             }
-        };
+            end <= gl && !gene[0].is_terminal() && gene[c.head_size..].iter().all(|x| x.is_terminal())
+        })
     }
-    fn second_pass<'a>(&'a self, pos: usize) -> Box<dyn Fn(&Vec<f32>, &DelayLine) -> f32 + 'a> {
-        let c = &self.codons[pos];
-        return match c {
-            Codon::Terminal(ref _t) => Box::new(move |args, delay_line| c.evaluate(0.0, 0.0, args, delay_line)),
-            Codon::Feedback(ref _f) => Box::new(move |args, delay_line| c.evaluate(0.0, 0.0, args, delay_line)),
-            _ => {
-                if c.get_arity() == 1{
-                    Box::new(move |args, delay_line|
-                        c.evaluate(self.second_pass(c.get_first_arg_position())(args,delay_line),
-                            0.0, args, delay_line))
-                }
-                else{
-                    Box::new(move |args, delay_line|
-                        c.evaluate(self.second_pass(c.get_first_arg_position())(args, delay_line),
-                            self.second_pass(c.get_first_arg_position() +1)(args, delay_line), args, delay_line))
+
+    #[test]
+    fn nary_operators_keep_genes_valid_test() {
+        let mut fs = FunctionSet::default();
+        fs.add_builtin("If");
+        fs.register("Sum4", 4, |a| a.iter().sum::<f32>() / 4.0);
+        let mut rng = StdRng::seed_from_u64(1);
+        let lf: Arc<dyn LinkingFunction> = Arc::new(crate::linking_function::Majority);
+        let mut chrs: Vec<Chromosome> = (0..20).map(|_| Chromosome::new(&mut rng, &fs, &lf, 3, 5, 3, 2)).collect();
+        // tail is h*(n_max-1)+1
+        assert_eq!(chrs[0].gene_length(), 5 + 16);
+        assert!(chrs.iter().all(is_valid));
+        for _ in 0..100 {
+            for c in chrs.iter_mut() {
+                *c = c.mutation(&mut rng, &fs, 3, 2, 0.3);
+                c.root_transposition(&mut rng, 1.0);
+                c.is_transposition(&mut rng, 1.0);
+                c.gene_transposition(&mut rng, 1.0);
+                c.inversion(&mut rng, 1.0);
+            }
+            for i in (0..chrs.len()).step_by(2) {
+                Chromosome::two_points_crossover(&mut chrs, i, i + 1, &mut rng);
+                Chromosome::one_point_crossover(&mut chrs, i, i + 1, &mut rng);
+                Chromosome::gene_recombination(&mut chrs, i, i + 1, &mut rng);
+            }
+            assert!(chrs.iter().all(is_valid));
+        }
+    }
+
+    // genes of the k-string without the linking function
+    fn genes(c: &Chromosome) -> Vec<String> {
+        let k = c.k_string();
+        k[k.find(" |").unwrap()..].split(" //").map(|g| g.trim().to_string()).collect()
+    }
+
+    #[test]
+    fn transpositions_test() {
+        let fs = FunctionSet::default();
+        let mut rng = StdRng::seed_from_u64(3);
+        let lf: Arc<dyn LinkingFunction> = Arc::new(crate::linking_function::Majority);
+        for _ in 0..50 {
+            let c = Chromosome::new(&mut rng, &fs, &lf, 3, 6, 2, 1);
+            let gl = c.gene_length();
+            let mut t = c.copy_to_new_generation();
+            t.is_transposition(&mut rng, 1.0);
+            // roots and tails are untouched, at most 3 head codons are inserted into one gene
+            let mut changed = 0;
+            for g in 0..3 {
+                assert_eq!(t.codons[g * gl].get_symbol(), c.codons[g * gl].get_symbol());
+                for j in g * gl + 6..(g + 1) * gl {
+                    assert_eq!(t.codons[j].get_symbol(), c.codons[j].get_symbol());
                 }
+                let head = |chr: &Chromosome| -> Vec<String> {
+                    chr.codons[g * gl..g * gl + 6].iter().map(|c| c.get_symbol().to_string()).collect()
+                };
+                let (before, after) = (head(&c), head(&t));
+                if before == after { continue }
+                changed += 1;
+                // the head shifted right by l from an insertion point on
+                assert!((1..6).any(|ip| (1..=3).any(|l| ip + l <= 6 && after[..ip] == before[..ip]
+                    && after[ip + l..] == before[ip..6 - l])));
+            }
+            assert!(changed <= 1);
+            let mut i = c.copy_to_new_generation();
+            i.inversion(&mut rng, 1.0);
+            assert!(is_valid(&i));
+            let mut i2 = i.copy_to_new_generation();
+            i2.inversion(&mut rng, 0.0);
+            assert_eq!(i2.k_string(), i.k_string());
+        }
+        let wsum = crate::linking_function::from_symbol("WSum").unwrap();
+        let c = Chromosome::new(&mut rng, &fs, &wsum, 3, 4, 2, 0);
+        let mut t = c.copy_to_new_generation();
+        t.gene_transposition(&mut rng, 1.0);
+        let (before, after) = (genes(&c), genes(&t));
+        // a gene moved to the front together with its weight, the others keep their order
+        let moved = before.iter().position(|g| *g == after[0]).unwrap();
+        assert!(moved > 0);
+        assert_eq!(t.weights[0], c.weights[moved]);
+        let rest: Vec<&String> = before.iter().enumerate().filter(|(i, _)| *i != moved).map(|(_, g)| g).collect();
+        assert_eq!(rest, after[1..].iter().collect::<Vec<&String>>());
+    }
+
+    #[test]
+    fn gene_crossovers_test() {
+        let fs = FunctionSet::default();
+        let mut rng = StdRng::seed_from_u64(4);
+        let wsum = crate::linking_function::from_symbol("WSum").unwrap();
+        let mut chrs: Vec<Chromosome> = (0..2).map(|_| Chromosome::new(&mut rng, &fs, &wsum, 4, 4, 2, 0)).collect();
+        let (a, b) = (genes(&chrs[0]), genes(&chrs[1]));
+        let wa = chrs[0].weights.clone();
+        Chromosome::gene_recombination(&mut chrs, 0, 1, &mut rng);
+        let (ra, rb) = (genes(&chrs[0]), genes(&chrs[1]));
+        let swapped: Vec<usize> = (0..4).filter(|g| ra[*g] != a[*g]).collect();
+        assert!(swapped.len() <= 1);
+        for g in 0..4 {
+            let s = swapped.contains(&g);
+            assert_eq!(ra[g], if s {b[g].clone()} else {a[g].clone()});
+            assert_eq!(rb[g], if s {a[g].clone()} else {b[g].clone()});
+        }
+        assert_eq!(chrs[0].weights.iter().zip(&wa).filter(|(x, y)| x != y).count(), swapped.len());
+        // one-point crossover twice at the same point restores the parents
+        let codons: Vec<String> = chrs[0].codons.iter().map(|c| c.get_symbol().to_string()).collect();
+        let mut r1 = StdRng::seed_from_u64(9);
+        Chromosome::one_point_crossover(&mut chrs, 0, 1, &mut r1);
+        let mut r2 = StdRng::seed_from_u64(9);
+        Chromosome::one_point_crossover(&mut chrs, 0, 1, &mut r2);
+        assert_eq!(codons, chrs[0].codons.iter().map(|c| c.get_symbol().to_string()).collect::<Vec<String>>());
+    }
+
+    #[test]
+    fn crossover_modes_test() {
+        let fs = FunctionSet::default();
+        let mut rng = StdRng::seed_from_u64(5);
+        let lf: Arc<dyn LinkingFunction> = Arc::new(crate::linking_function::Majority);
+        for _ in 0..50 {
+            let mut chrs: Vec<Chromosome> = (0..2).map(|_| Chromosome::new(&mut rng, &fs, &lf, 4, 4, 2, 1)).collect();
+            let gl = chrs[0].gene_length();
+            let (a, b) = (genes(&chrs[0]), genes(&chrs[1]));
+            Chromosome::two_points_crossover_with(&mut chrs, 0, 1, CrossoverMode::Genes, &mut rng);
+            // every gene comes whole from one of the parents, the partner got the other one
+            let (ra, rb) = (genes(&chrs[0]), genes(&chrs[1]));
+            for g in 0..4 {
+                assert!((ra[g] == a[g] && rb[g] == b[g]) || (ra[g] == b[g] && rb[g] == a[g]));
+            }
+            let before: Vec<String> = chrs[0].codons.iter().map(|c| c.get_symbol().to_string()).collect();
+            let other: Vec<String> = chrs[1].codons.iter().map(|c| c.get_symbol().to_string()).collect();
+            Chromosome::two_points_crossover_with(&mut chrs, 0, 1, CrossoverMode::InGene, &mut rng);
+            // changed codons lie within a single gene
+            let changed: Vec<usize> = (0..before.len()).filter(|i| chrs[0].codons[*i].get_symbol() != before[*i]).collect();
+            if let (Some(f), Some(l)) = (changed.first(), changed.last()) {
+                assert_eq!(f / gl, l / gl);
+            }
+            assert!(changed.iter().all(|i| chrs[0].codons[*i].get_symbol() == other[*i]));
+            assert!(chrs.iter().all(is_valid));
+        }
+        // chromosomes of different structure are left alone
+        let mut chrs = vec![Chromosome::new(&mut rng, &fs, &lf, 2, 4, 2, 1), Chromosome::new(&mut rng, &fs, &lf, 2, 5, 2, 1)];
+        let k = chrs[0].k_string();
+        Chromosome::two_points_crossover(&mut chrs, 0, 1, &mut rng);
+        Chromosome::one_point_crossover(&mut chrs, 0, 1, &mut rng);
+        Chromosome::gene_recombination(&mut chrs, 0, 1, &mut rng);
+        assert_eq!(chrs[0].k_string(), k);
+    }
+
+    #[test]
+    fn constants_test() {
+        let mut fs = FunctionSet::default();
+        fs.set_constants(5, -2.0, 2.0);
+        let mut rng = StdRng::seed_from_u64(6);
+        let lf: Arc<dyn LinkingFunction> = Arc::new(crate::linking_function::Majority);
+        let mut chrs: Vec<Chromosome> = (0..10).map(|_| Chromosome::new(&mut rng, &fs, &lf, 3, 4, 2, 1)).collect();
+        let tl = chrs[0].tail_size;
+        assert!(chrs.iter().all(|c| c.dc.len() == 3 * tl && c.constants.len() == 15));
+        assert!(chrs.iter().any(|c| c.codons.iter().any(|x| matches!(x, Codon::Constant))));
+        for _ in 0..50 {
+            for c in chrs.iter_mut() {
+                *c = c.mutation(&mut rng, &fs, 2, 1, 0.3);
+                c.dc_transposition(&mut rng, 1.0);
+                c.constant_mutation(&mut rng, &fs, 0.5);
+                c.gene_transposition(&mut rng, 1.0);
+            }
+            for i in (0..chrs.len()).step_by(2) {
+                Chromosome::two_points_crossover(&mut chrs, i, i + 1, &mut rng);
+                Chromosome::one_point_crossover(&mut chrs, i, i + 1, &mut rng);
+                Chromosome::gene_recombination(&mut chrs, i, i + 1, &mut rng);
+            }
+            assert!(chrs.iter().all(is_valid));
+            assert!(chrs.iter().all(|c| c.dc.len() == 3 * tl && c.dc.iter().all(|d| *d < 5)));
+            assert!(chrs.iter().all(|c| c.constants.iter().all(|x| (-2.0..2.0).contains(x))));
+        }
+        // the n-th `?` of a gene reads the constant at the n-th index of its Dc domain
+        let c = &chrs[0];
+        let values = c.constant_values();
+        let gl = c.gene_length();
+        for (g, vals) in values.iter().enumerate() {
+            let cnt = c.codons[g * gl..(g + 1) * gl].iter().filter(|x| matches!(x, Codon::Constant)).count();
+            assert_eq!(vals.len(), cnt.min(tl));
+            for (k, v) in vals.iter().enumerate() {
+                assert_eq!(*v, c.constants[g * 5 + c.dc[g * tl + k]]);
+            }
+        }
+        // Dc domain and constants are shown with their gene and follow it in crossover
+        let k = c.k_string();
+        assert_eq!(k.matches(" Dc(").count(), 3);
+        assert!(k.contains(&format!(" C({}", c.constants[0])));
+        let (a, b) = (genes(&chrs[0]), genes(&chrs[1]));
+        Chromosome::two_points_crossover_with(&mut chrs, 0, 1, CrossoverMode::Genes, &mut rng);
+        let (ra, rb) = (genes(&chrs[0]), genes(&chrs[1]));
+        for g in 0..3 {
+            assert!((ra[g] == a[g] && rb[g] == b[g]) || (ra[g] == b[g] && rb[g] == a[g]));
+        }
+        // no constants - no Dc domain
+        let c = Chromosome::new(&mut rng, &FunctionSet::default(), &lf, 3, 4, 2, 1);
+        assert!(c.dc.is_empty() && c.constants.is_empty() && !c.k_string().contains("Dc("));
+    }
+
+    #[test]
+    fn dc_transposition_test() {
+        let mut fs = FunctionSet::default();
+        fs.set_constants(10, -1.0, 1.0);
+        let mut rng = StdRng::seed_from_u64(7);
+        let lf: Arc<dyn LinkingFunction> = Arc::new(crate::linking_function::Majority);
+        for _ in 0..50 {
+            let c = Chromosome::new(&mut rng, &fs, &lf, 2, 5, 2, 0);
+            let tl = c.tail_size;
+            let mut t = c.copy_to_new_generation();
+            t.dc_transposition(&mut rng, 1.0);
+            // only the Dc domain of one gene changes
+            let changed: Vec<usize> = (0..t.dc.len()).filter(|i| t.dc[*i] != c.dc[*i]).collect();
+            if let (Some(f), Some(l)) = (changed.first(), changed.last()) {
+                assert_eq!(f / tl, l / tl);
             }
+            assert_eq!(t.k_string().split(" Dc(").next(), c.k_string().split(" Dc(").next());
+            assert_eq!(t.constants, c.constants);
+        }
+    }
+
+    #[test]
+    fn homeotic_test() {
+        let fs = FunctionSet::default();
+        let mut rng = StdRng::seed_from_u64(8);
+        let wsum = crate::linking_function::from_symbol("WSum").unwrap();
+        let mut chrs: Vec<Chromosome> = (0..10).map(|_| {
+            let mut c = Chromosome::new(&mut rng, &fs, &wsum, 3, 4, 2, 1);
+            c.set_homeotic(&mut rng, &fs, 2, 3);
+            c
+        }).collect();
+        // roots are functions, tails reference ADFs only
+        let valid = |c: &Chromosome| {
+            let hl = c.homeotic_length();
+            is_valid(c) && c.weights.len() == 2 && (0..2).all(|h| {
+                let gene = &c.homeotic[h * hl..(h + 1) * hl];
+                !gene[0].is_terminal() && gene[3..].iter().all(|x| matches!(x, Codon::Terminal(t) if t.i < 3))
+                    && gene.iter().all(|x| !matches!(x, Codon::Feedback(_) | Codon::Constant))
+            })
         };
+        assert!(chrs.iter().all(|c| c.homeotic.len() == 2 * 7 && valid(c)));
+        let homeotic = |c: &Chromosome| c.homeotic.iter().map(|x| x.get_symbol().to_string()).collect::<Vec<String>>();
+        // operators of genes apply to homeotic genes too
+        let mut changed = [0; 6];
+        let modes = [CrossoverMode::Free, CrossoverMode::Genes, CrossoverMode::InGene];
+        for _ in 0..50 {
+            for c in chrs.iter_mut() {
+                *c = c.mutation(&mut rng, &fs, 2, 1, 0.3);
+                c.gene_transposition(&mut rng, 1.0);
+                let ops: [fn(&mut Chromosome, &mut StdRng); 3] = [|c, r| c.root_transposition(r, 1.0),
+                    |c, r| c.is_transposition(r, 1.0), |c, r| c.inversion(r, 1.0)];
+                for (k, op) in ops.iter().enumerate() {
+                    let before = homeotic(c);
+                    op(c, &mut rng);
+                    if homeotic(c) != before { changed[k] += 1 }
+                }
+            }
+            for i in (0..chrs.len()).step_by(2) {
+                let before = homeotic(&chrs[i]);
+                Chromosome::one_point_crossover(&mut chrs, i, i + 1, &mut rng);
+                if homeotic(&chrs[i]) != before { changed[3] += 1 }
+                for mode in modes {
+                    let before = homeotic(&chrs[i]);
+                    Chromosome::two_points_crossover_with(&mut chrs, i, i + 1, mode, &mut rng);
+                    if homeotic(&chrs[i]) != before { changed[if mode == CrossoverMode::InGene {5} else {4}] += 1 }
+                }
+                Chromosome::gene_recombination(&mut chrs, i, i + 1, &mut rng);
+            }
+            assert!(chrs.iter().all(valid));
+        }
+        assert!(changed.iter().all(|n| *n > 0), "{:?}", changed);
+        let k = chrs[0].k_string();
+        assert_eq!(k.matches(" ##").count(), 1);
+        assert_eq!(k.split(" ##").nth(1).unwrap().matches(" //").count(), 1);
+        // homeotic genes are compiled with the ADFs
+        let p = chrs[0].translate();
+        assert!(p.size() > 3);
+        // no homeotic genes - weights belong to genes again
+        chrs[0].set_homeotic(&mut rng, &fs, 0, 3);
+        assert!(chrs[0].homeotic.is_empty() && chrs[0].weights.len() == 3 && !chrs[0].k_string().contains(" ##"));
+    }
+
+    fn sample_market() -> crate::fitness_evaluator::FitnessFunction {
+        let args: Vec<Vec<f32>> = (0..60).map(|i| vec![(i as f32 * 0.3).sin(), (i as f32 * 0.7).cos()]).collect();
+        let results = (0..60).map(|i| (i as f32 * 0.5).sin()).collect();
+        crate::fitness_evaluator::FitnessFunction::new(args, results, 0.5)
     }
-}
\ No newline at end of file
+
+    // chromosomes with every optional part: weights, windowed state functions, feedback,
+    // constants and homeotic genes
+    fn samples(fs: &FunctionSet) -> Vec<Chromosome> {
+        let mut rng = StdRng::seed_from_u64(10);
+        let mut rnc = fs.clone();
+        rnc.set_constants(4, -1.0, 1.0);
+        let mut chrs = Vec::new();
+        for (linker, set, homeotic) in [("Maj", fs, 0), ("WSum", fs, 0), ("Mean~4", &rnc, 0), ("WSum", &rnc, 2), ("Gate", fs, 1)] {
+            let lf = crate::linking_function::from_symbol(linker).unwrap();
+            for _ in 0..5 {
+                let mut c = Chromosome::new(&mut rng, set, &lf, 3, 4, 2, 2);
+                if homeotic > 0 { c.set_homeotic(&mut rng, set, homeotic, 3) }
+                chrs.push(c);
+            }
+        }
+        chrs
+    }
+
+    #[test]
+    fn from_k_string_test() {
+        let mut fs = FunctionSet::default();
+        fs.add_builtin("Sma");
+        fs.set_windows(&[3, 12]);
+        fs.register("Half", 1, |a| a[0] * 0.5);
+        let fe = sample_market();
+        for c in samples(&fs) {
+            let k = c.k_string();
+            let r = Chromosome::from_k_string(&k, &fs).unwrap();
+            assert_eq!(r.k_string(), k);
+            assert_eq!(r.equity(2, false, &fe), c.equity(2, false, &fe));
+        }
+        let k = samples(&fs)[0].k_string();
+        // unknown function, no linking function, unknown linking function
+        assert!(Chromosome::from_k_string(&k, &FunctionSet::new()).is_none());
+        assert!(Chromosome::from_k_string(&k[k.find(" |").unwrap()..], &fs).is_none());
+        assert!(Chromosome::from_k_string(&k.replacen("Maj", "Foo", 1), &fs).is_none());
+        // genes of different length, a terminal root
+        assert!(Chromosome::from_k_string(&k.replacen(" //", " |0| //", 1), &fs).is_none());
+        let gene = k.split(" //").nth(1).unwrap();
+        let root = gene.split_whitespace().next().unwrap();
+        assert!(Chromosome::from_k_string(&k.replacen(gene, &gene.replacen(root, "|1|", 1), 1), &fs).is_none());
+        // weights of a weighted linking function are required
+        let w = samples(&fs)[5].k_string();
+        assert!(Chromosome::from_k_string(&format!("WSum{}", &w[w.find(" |").unwrap()..]), &fs).is_none());
+    }
+
+    #[test]
+    fn serde_test() {
+        let mut fs = FunctionSet::default();
+        fs.add_builtin("Ema");
+        let fe = sample_market();
+        for mut c in samples(&fs) {
+            c.fitness = 0.25;
+            let text = toml::to_string(&c).unwrap();
+            let r: Chromosome = toml::from_str(&text).unwrap();
+            assert_eq!(r.k_string(), c.k_string());
+            assert_eq!(r.fitness, 0.25);
+            assert_eq!(r.equity(2, true, &fe), c.equity(2, true, &fe));
+        }
+        let text = toml::to_string(&samples(&fs)[0]).unwrap();
+        // parts that do not fit together
+        assert!(toml::from_str::<Chromosome>(&text.replacen("head_size = 4", "head_size = 3", 1)).is_err());
+        assert!(toml::from_str::<Chromosome>(&text.replacen("linker = \"Maj\"", "linker = \"WSum\"", 1)).is_err());
+        // user functions, one of them replacing a built-in, are recorded and need their function set
+        let mut user = FunctionSet::default();
+        user.register("Half", 1, |a| a[0] * 0.5);
+        user.register("Avg", 2, |a| a[0].max(a[1]));
+        let maj = crate::linking_function::from_symbol("Maj").unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        let c = (0..50).map(|_| Chromosome::new(&mut rng, &user, &maj, 2, 6, 2, 0))
+            .find(|c| c.user_functions().len() == 2).unwrap();
+        let text = toml::to_string(&c).unwrap();
+        let err = toml::from_str::<Chromosome>(&text).err().unwrap();
+        assert!(err.to_string().contains("needs its function set"));
+        let r = WithFunctionSet(&user).deserialize(&mut toml::Deserializer::new(&text)).unwrap();
+        assert_eq!(r.k_string(), c.k_string());
+        assert_eq!(r.equity(0, true, &fe), c.equity(0, true, &fe));
+        // the built-in Avg is not taken for the user one
+        let err = WithFunctionSet(&fs).deserialize(&mut toml::Deserializer::new(&text)).err().unwrap();
+        assert!(err.to_string().contains("no user function"));
+    }
+
+    #[test]
+    fn weighted_linker_test() {
+        let fs = FunctionSet::default();
+        let mut rng = StdRng::seed_from_u64(2);
+        let maj = crate::linking_function::from_symbol("Maj").unwrap();
+        assert!(Chromosome::new(&mut rng, &fs, &maj, 3, 4, 2, 0).weights.is_empty());
+        let wsum = crate::linking_function::from_symbol("WSum").unwrap();
+        let c = Chromosome::new(&mut rng, &fs, &wsum, 3, 4, 2, 0);
+        assert_eq!(c.weights.len(), 3);
+        assert!(c.weights.iter().all(|w| (-1.0..1.0).contains(w)));
+        assert!(c.k_string().starts_with(&format!("WSum({},{},{}) |", c.weights[0], c.weights[1], c.weights[2])));
+        let m = c.mutation(&mut rng, &fs, 2, 0, 1.0);
+        assert!(m.weights.iter().zip(&c.weights).all(|(a, b)| a != b));
+    }
+}
@@ -0,0 +1,470 @@
+use crate::codons::Codon;
+use crate::expression::{rust_name, Expression, Format};
+use crate::functions::Primitive;
+
+// bodies of built-in functions with arguments a0, a1, a2; copies of functions.rs, so generated
+// models compute the same values bit for bit
+const FUNCTIONS: &[(&str, &str)] = &[
+    ("Min", "a0.min(a1)"),
+    ("Max", "a0.max(a1)"),
+    ("Avg", "(a0+a1)/2.0"),
+    ("Neg", "-a0"),
+    ("Sgn", "sign(a0)"),
+    ("Thr", "if a0.abs() > a1.abs() {sign(a0)} else {0.0}"),
+    ("Mul", "a0*a1"),
+    ("Add", "clip(a0+a1)"),
+    ("Sub", "clip(a0-a1)"),
+    ("Abs", "a0.abs()"),
+    ("And", "from_truth(to_truth(a0)*to_truth(a1))"),
+    ("Or", "let (x, y) = (to_truth(a0), to_truth(a1));\n    from_truth(x + y - x*y)"),
+    ("Gt", "if a0 > a1 {1.0} else {-1.0}"),
+    ("Lt", "if a0 < a1 {1.0} else {-1.0}"),
+    ("If", "if a0 > 0.0 {a1} else {a2}"),
+    ("Med3", "a0.max(a1).min(a0.min(a1).max(a2))"),
+];
+
+// (current input, previous inputs, previous output) -> output of built-in state functions,
+// copies of state_functions.rs
+const STATE_FUNCTIONS: &[(&str, &str)] = &[
+    ("Delay", "fn delay_op(_curr: f32, prev: &DelayLine, _out: f32) -> f32 {\n    prev.get_shifted_back(0)\n}\n"),
+    ("Ma2", "fn ma2_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {\n    0.5*(curr + prev.get_shifted_back(0))\n}\n"),
+    ("Diff", "fn diff_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {\n    clip(curr - prev.get_shifted_back(0))\n}\n"),
+    ("Lag", "fn lag_op(_curr: f32, prev: &DelayLine, _out: f32) -> f32 {\n    prev.get_shifted_back(prev.capacity - 1)\n}\n"),
+    ("Sma", "fn sma_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {\n    \
+             rolling(curr, prev).sum::<f32>() / prev.capacity as f32\n}\n"),
+    ("Ema", "fn ema_op(curr: f32, prev: &DelayLine, out: f32) -> f32 {\n    \
+             out + 2.0 / (prev.capacity as f32 + 1.0) * (curr - out)\n}\n"),
+    ("RMin", "fn rmin_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {\n    rolling(curr, prev).fold(curr, f32::min)\n}\n"),
+    ("RMax", "fn rmax_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {\n    rolling(curr, prev).fold(curr, f32::max)\n}\n"),
+    ("RSum", "fn rsum_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {\n    clip(rolling(curr, prev).sum::<f32>())\n}\n"),
+    ("Mom", "fn mom_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {\n    \
+             clip(curr - prev.get_shifted_back(prev.capacity - 1))\n}\n"),
+    ("ZSc", "fn zsc_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {\n    \
+             let n = prev.capacity as f32;\n    \
+             let mean = rolling(curr, prev).sum::<f32>() / n;\n    \
+             let var = rolling(curr, prev).map(|x| (x - mean) * (x - mean)).sum::<f32>() / n;\n    \
+             if var <= 0.0 {0.0} else {clip(0.5 * (curr - mean) / var.sqrt())}\n}\n"),
+];
+
+// (symbol, evaluate, level) of built-in linking functions with outputs o and weights w,
+// copies of linking_function.rs
+const LINKERS: &[(&str, &str, &str)] = &[
+    ("Maj", "o.iter().sum::<f32>().signum()", "mean_of(o)"),
+    ("WSum", "sign(o.iter().zip(w).map(|(o, w)| o * w).sum::<f32>())",
+     "let norm = w.iter().map(|w| w.abs()).sum::<f32>();\n    \
+      if norm > 0.0 {o.iter().zip(w).map(|(o, w)| o * w).sum::<f32>() / norm} else {0.0}"),
+    ("Mean", "mean_of(o)", "mean_of(o)"),
+    ("Unan", "if o.iter().all(|x| *x > 0.0) {1.0}\n    else if o.iter().all(|x| *x < 0.0) {-1.0}\n    else {0.0}",
+     "if o.iter().all(|x| *x > 0.0) || o.iter().all(|x| *x < 0.0) {mean_of(o)} else {0.0}"),
+    ("Gate", "if o.len() < 2 { return sign(o[0]) }\n    if o[0] > 0.0 { sign(o[1..].iter().sum::<f32>()) } else {0.0}",
+     "if o.len() < 2 { return o[0] }\n    if o[0] > 0.0 { mean_of(&o[1..]) } else {0.0}"),
+];
+
+// helpers of the code above, included if called
+const HELPERS: &[(&str, &str)] = &[
+    ("clip", "fn clip(x: f32) -> f32 {\n    x.clamp(-1.0, 1.0)\n}\n"),
+    ("sign", "fn sign(x: f32) -> f32 {\n    if x > 0.0 {1.0} else if x < 0.0 {-1.0} else {0.0}\n}\n"),
+    ("to_truth", "fn to_truth(x: f32) -> f32 {\n    0.5*(x + 1.0)\n}\n"),
+    ("from_truth", "fn from_truth(t: f32) -> f32 {\n    2.0*t - 1.0\n}\n"),
+    ("mean_of", "fn mean_of(x: &[f32]) -> f32 {\n    x.iter().sum::<f32>() / x.len() as f32\n}\n"),
+    ("rolling", "fn rolling(curr: f32, prev: &DelayLine) -> impl Iterator<Item = f32> + '_ {\n    \
+                 std::iter::once(curr).chain((0..prev.capacity - 1).map(move |k| prev.get_shifted_back(k)))\n}\n"),
+];
+
+// memories of state functions and the delay line of previous outputs, copies of the library
+const MEMORY: &str = "\
+// memory of a state function: previous inputs and previous output
+#[allow(dead_code)]
+struct Memory {
+    inputs: DelayLine,
+    output: f32,
+}
+
+#[allow(dead_code)]
+impl Memory {
+    fn new(window: usize) -> Self {
+        Memory { inputs: DelayLine::new(window), output: 0.0 }
+    }
+
+    fn step(&mut self, curr: f32, op: fn(f32, &DelayLine, f32) -> f32) -> f32 {
+        let ret = op(curr, &self.inputs, self.output);
+        self.inputs.push(curr);
+        self.output = ret;
+        ret
+    }
+}
+
+struct DelayLine {
+    capacity: usize,
+    idx: usize,
+    buff: Vec<f32>,
+}
+
+impl DelayLine {
+    fn new(capacity: usize) -> Self {
+        let c = if capacity < 1 {1} else {capacity};
+        DelayLine { capacity: c, idx: 0, buff: vec![0.0; c] }
+    }
+
+    fn push(&mut self, v: f32) {
+        self.buff[self.idx] = v;
+        self.idx += 1;
+        if self.idx == self.capacity {self.idx = 0}
+    }
+
+    fn get_shifted_back(&self, steps: usize) -> f32 {
+        let i = (steps + 1) % self.capacity;
+        self.buff[if i > self.idx {self.idx + self.capacity - i} else {self.idx - i}]
+    }
+}
+";
+
+// standalone Rust source of an expression: a struct `name` with new() and step(), which gives the
+// output of the chromosome for a row of inputs like Program::eval with a delay line of max_delay
+// previous outputs; None if it uses user functions, user linking functions or anything missing
+// from the tables above
+pub(crate) fn module(expression: &Expression, name: &str, max_delay: usize) -> Option<String> {
+    let (model, windows) = expression.rust_function();
+    let codons = expression.codons();
+    let mut functions: Vec<&Primitive> = Vec::new();
+    let mut state_functions: Vec<(&str, &str)> = Vec::new();
+    for c in &codons {
+        match c {
+            Codon::Function(f) => {
+                if !f.fd.builtin || !FUNCTIONS.iter().any(|(s, _)| *s == f.fd.symbol) { return None }
+                if !functions.iter().any(|p| p.symbol == f.fd.symbol) { functions.push(&f.fd) }
+            }
+            Codon::StateFunction(f) => {
+                if !STATE_FUNCTIONS.iter().any(|(s, _)| *s == f.fd.symbol) { return None }
+                if !state_functions.iter().any(|(s, _)| *s == f.symbol) { state_functions.push((&f.symbol, f.fd.symbol)) }
+            }
+            _ => {}
+        }
+    }
+    let mut definitions = String::new();
+    for (symbol, body) in FUNCTIONS {
+        if let Some(f) = functions.iter().find(|f| f.symbol == *symbol) {
+            let args: Vec<String> = (0..f.arity).map(|i| format!("a{}: f32", i)).collect();
+            definitions.push_str(&format!("\nfn {}({}) -> f32 {{\n    {}\n}}\n", rust_name(symbol), args.join(", "), body));
+        }
+    }
+    for (symbol, base) in &state_functions {
+        definitions.push_str(&format!("\nfn {}(x: f32, m: &mut Memory) -> f32 {{\n    m.step(x, {}_op)\n}}\n",
+                                      rust_name(symbol), rust_name(base)));
+    }
+    for (symbol, op) in STATE_FUNCTIONS {
+        if state_functions.iter().any(|(_, base)| base == symbol) {
+            definitions.push('\n');
+            definitions.push_str(op);
+        }
+    }
+    definitions.push_str(&linker(expression)?);
+    for (helper, code) in HELPERS {
+        if definitions.contains(&format!("{}(", helper)) {
+            definitions.push('\n');
+            definitions.push_str(code);
+        }
+    }
+    // feedback z(i) reads previous output i
+    let feedback = codons.iter().filter_map(|c| match c {
+        Codon::Feedback(f) => Some(f.i + 1),
+        _ => None,
+    }).max().unwrap_or(0);
+    let memory: Vec<String> = windows.iter().map(|w| format!("Memory::new({})", w)).collect();
+    let text = expression.render(Format::Text).replace('\n', "\n// ");
+    Some(format!("// {text}\n\
+                  pub struct {name} {{\n    \
+                      memory: Vec<Memory>,\n    \
+                      // previous outputs\n    \
+                      delay: DelayLine,\n\
+                  }}\n\n\
+                  impl {name} {{\n    \
+                      pub fn new() -> Self {{\n        \
+                          {name} {{ memory: vec![{memory}], delay: DelayLine::new({max_delay}) }}\n    \
+                      }}\n\n    \
+                      // output for a row of inputs\n    \
+                      pub fn step(&mut self, args: &[f32]) -> f32 {{\n        \
+                          let z: [f32; {feedback}] = std::array::from_fn(|i| self.delay.get_shifted_back(i));\n        \
+                          let s = model(args, &z, &mut self.memory);\n        \
+                          self.delay.push(s);\n        \
+                          s\n    \
+                      }}\n\
+                  }}\n\n\
+                  impl Default for {name} {{\n    \
+                      fn default() -> Self {{\n        \
+                          Self::new()\n    \
+                      }}\n\
+                  }}\n\n\
+                  {model}{definitions}\n{MEMORY}",
+                 memory = memory.join(", ")))
+}
+
+// linking function and, for continuous positions, the level of the inner one
+fn linker(expression: &Expression) -> Option<String> {
+    let symbol = expression.linker.symbol();
+    let (inner, levels) = match symbol.split_once('~') {
+        Some((inner, levels)) => (inner, Some(if levels.is_empty() {0} else {levels.parse::<usize>().ok()?})),
+        None => (symbol.as_str(), None),
+    };
+    let (_, evaluate, level) = LINKERS.iter().find(|(s, _, _)| *s == inner)?;
+    let weighted = !expression.weights.is_empty();
+    if weighted != expression.linker.weighted() {
+        return None;
+    }
+    let (params, args) = if weighted {("o: &[f32], w: &[f32]", "o, w")} else {("o: &[f32]", "o")};
+    let name = rust_name(inner);
+    Some(match levels {
+        None => format!("\nfn {}({}) -> f32 {{\n    {}\n}}\n", name, params, evaluate),
+        Some(levels) => {
+            let round = if levels > 0 {format!("(x * {:?}).round() / {:?}", levels as f32, levels as f32)} else {"x".to_string()};
+            format!("\nfn {}_level({}) -> f32 {{\n    {}\n}}\n\nfn {}({}) -> f32 {{\n    let x = {}_level({}).clamp(-1.0, 1.0);\n    {}\n}}\n",
+                    name, params, level, rust_name(&symbol), params, name, args, round)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use super::*;
+    use crate::chromosome::Chromosome;
+    use crate::delay_line::DelayLine;
+    use crate::function_set::FunctionSet;
+    use crate::functions::{ne, Function, FunctionDescription, EXT_FREGISTRY, FREGISTRY};
+    use crate::state_functions::{StateFunction, EXT_SFREGISTRY, SFREGISTRY};
+
+    // generated by Chromosome::to_rust from the chromosomes below
+    mod wsum_model {
+        include!("../testdata/wsum_model.rs");
+    }
+    mod gate_model {
+        include!("../testdata/gate_model.rs");
+    }
+    mod maj_model {
+        include!("../testdata/maj_model.rs");
+    }
+    mod mean_model {
+        include!("../testdata/mean_model.rs");
+    }
+    mod unan_model {
+        include!("../testdata/unan_model.rs");
+    }
+    // generated by primitives() below
+    mod primitives {
+        include!("../testdata/primitives.rs");
+    }
+
+    // weighted linking, constants and feedback
+    const WSUM: &str = "WSum(0.6095052,0.89461756,-0.33011532) \
+        |Delay| |Ma2| |Sma2| |Diff| |If| |2| |2| |2| |0| |0| |2| |0| |1| |1| |1| |0| \
+        Dc(0,1,1,0,0,0,0,0,1,0,2) C(0.23678505,-1.4734744,1.3482606,0.89525485) \
+        // |Add| |And| |Sgn| |ZSc5| |Neg| |0| |?| |1| |z(0)| |0| |z(2)| |?| |2| |z(2)| |z(2)| |0| \
+        Dc(0,0,1,1,3,1,0,1,2,1,0) C(0.6539364,-1.0907815,0.35245228,0.58656645) \
+        // |Lag2| |Thr| |Mom3| |Lt| |z(1)| |0| |z(1)| |0| |2| |z(1)| |z(0)| |?| |0| |0| |1| |0| \
+        Dc(0,2,2,1,0,3,3,2,1,2,3) C(0.18572688,0.7404151,-0.19772136,0.21424878)";
+    // continuous linking and homeotic genes
+    const GATE: &str = "Gate~4 \
+        |RMax5| |Max| |Diff| |RMin2| |0| |0| |1| |?| |2| |z(0)| |0| |2| |z(1)| \
+        Dc(0,2,2,1,1,3,0,1,1) C(-0.3640505,1.3126454,-0.5515362,0.7460604) \
+        // |Abs| |Mul| |0| |ZSc2| |0| |1| |?| |0| |z(0)| |z(1)| |1| |z(1)| |?| \
+        Dc(1,3,2,2,1,1,2,3,1) C(-0.73782885,0.00016093254,-1.1029887,-0.4743719) \
+        // |Lag5| |Or| |Min| |Add| |2| |?| |z(1)| |0| |0| |?| |2| |1| |?| \
+        Dc(3,1,2,0,0,1,2,2,2) C(-0.17173326,1.2444077,0.8009362,-0.3954388) \
+        ## |Ema3| |RMax2| |Mom3| |0| |2| |1| |2| |1| |0| |2| // |RSum2| |Sub| |Med3| |1| |0| |2| |2| |2| |2| |2|";
+    // linkers without `~`, Avg and Gt
+    const MAJ: &str = "Maj \
+        |Avg| |Neg| |Avg| |?| |z(0)| |0| |z(1)| |1| |2| |z(0)| |?| |0| |0| \
+        Dc(0,3,1,1,0,3,3,0,2) C(0.42178488,1.153894,-0.02777338,0.2962067) \
+        // |Avg| |Neg| |Sub| |Sub| |0| |?| |1| |?| |0| |2| |0| |z(1)| |z(0)| \
+        Dc(0,0,3,1,2,3,1,3,0) C(-0.9757658,-0.8975086,-0.06790781,0.11010611) \
+        // |Neg| |Ma2| |Gt| |If| |2| |1| |?| |z(0)| |?| |0| |1| |z(1)| |?| \
+        Dc(1,1,1,2,2,0,2,1,3) C(-0.7101846,-0.007266283,0.8468299,-1.1151574)";
+    const MEAN: &str = "Mean \
+        |Neg| |And| |Mul| |?| |z(0)| |0| |z(1)| |1| |2| |z(0)| |?| |0| |0| \
+        Dc(1,1,0,3,3,0,2,0,0) C(-0.9757658,-0.8975086,-0.06790781,0.11010611) \
+        // |Min| |Ma2| |Thr| |Diff| |0| |?| |1| |?| |0| |2| |0| |z(1)| |z(0)| \
+        Dc(3,1,2,3,1,3,0,1,1) C(-0.7101846,-0.007266283,0.8468299,-1.1151574) \
+        // |Sub| |Ema20| |Sub| |RMax20| |0| |0| |z(0)| |?| |0| |1| |z(1)| |?| |?| \
+        Dc(1,2,2,0,2,1,3,1,0) C(-0.24954164,0.96975994,0.507318,-0.5651214)";
+    const UNAN: &str = "Unan~3 \
+        |Lag5| |Delay| |And| |RMin20| |1| |?| |2| |2| |?| |z(0)| |?| |0| |2| \
+        Dc(0,0,1,2,0,2,1,1,1) C(-0.19805646,0.7589371,-1.1188062,-0.8949076) \
+        // |And| |Sma20| |Diff| |Max| |z(0)| |?| |2| |?| |0| |0| |2| |2| |1| \
+        Dc(1,0,2,3,3,1,0,2,3) C(0.83710575,0.6101861,1.408669,0.9100299) \
+        // |Sma2| |Lt| |z(1)| |Gt| |0| |0| |1| |2| |1| |0| |1| |2| |z(0)| \
+        Dc(1,2,2,2,1,1,1,0,0) C(1.138174,-0.4912616,-0.3837304,-0.8566575)";
+
+    fn chromosome(k: &str) -> Chromosome {
+        let mut fs = FunctionSet::builtins();
+        fs.set_constants(4, -1.5, 1.5);
+        Chromosome::from_k_string(k, &fs).unwrap()
+    }
+
+    // outputs of translate() like evaluators compute them; inputs exceed [-1,1] at times
+    fn outputs(c: &Chromosome, max_delay: usize, rows: &[Vec<f32>]) -> Vec<f32> {
+        let mut program = c.translate();
+        let mut dl = DelayLine::new(max_delay);
+        rows.iter().map(|row| {
+            let s = program.eval(row, &dl);
+            dl.push(s);
+            s
+        }).collect()
+    }
+
+    fn rows() -> Vec<Vec<f32>> {
+        let mut rng = StdRng::seed_from_u64(5);
+        (0..500).map(|_| (0..3).map(|_| rng.gen_range(-1.2..1.2)).collect()).collect()
+    }
+
+    fn changes(outputs: &[f32]) -> usize {
+        outputs.windows(2).filter(|w| w[0] != w[1]).count()
+    }
+
+    #[test]
+    fn module_test() {
+        assert_eq!(chromosome(WSUM).to_rust("WSumModel", 3).unwrap(), include_str!("../testdata/wsum_model.rs"));
+        assert_eq!(chromosome(GATE).to_rust("GateModel", 2).unwrap(), include_str!("../testdata/gate_model.rs"));
+        assert_eq!(chromosome(MAJ).to_rust("MajModel", 2).unwrap(), include_str!("../testdata/maj_model.rs"));
+        assert_eq!(chromosome(MEAN).to_rust("MeanModel", 2).unwrap(), include_str!("../testdata/mean_model.rs"));
+        assert_eq!(chromosome(UNAN).to_rust("UnanModel", 2).unwrap(), include_str!("../testdata/unan_model.rs"));
+        let mut fs = FunctionSet::default();
+        fs.register("Half", 1, |a| a[0] * 0.5);
+        let c = Chromosome::from_k_string("Maj |Half| |0| |1|", &fs).unwrap();
+        assert!(c.to_rust("Model", 1).is_none());
+        let mut c = Chromosome::from_k_string("Maj |Neg| |0| |1|", &fs).unwrap();
+        assert!(c.to_rust("Model", 1).unwrap().contains("fn neg(a0: f32) -> f32 {\n    -a0\n}"));
+        // a built-in missing from the tables
+        let fd = FunctionDescription { symbol: "Foo", arity: 1, op: ne };
+        c.codons[0] = Codon::Function(Function::new(Primitive::builtin(&fd)));
+        assert!(c.to_rust("Model", 1).is_none());
+    }
+
+    #[test]
+    fn tables_test() {
+        for fd in FREGISTRY.iter().chain(EXT_FREGISTRY) {
+            assert!(FUNCTIONS.iter().any(|(s, _)| *s == fd.symbol), "no code of {}", fd.symbol);
+        }
+        for sfd in SFREGISTRY.iter().chain(EXT_SFREGISTRY) {
+            assert!(STATE_FUNCTIONS.iter().any(|(s, _)| *s == sfd.symbol), "no code of {}", sfd.symbol);
+        }
+        for symbol in crate::linking_function::SYMBOLS {
+            assert!(LINKERS.iter().any(|(s, _, _)| s == symbol), "no code of {}", symbol);
+        }
+        // and no code of anything else
+        assert_eq!(FUNCTIONS.len(), FREGISTRY.len() + EXT_FREGISTRY.len());
+        assert_eq!(STATE_FUNCTIONS.len(), SFREGISTRY.len() + EXT_SFREGISTRY.len());
+        assert_eq!(LINKERS.len(), crate::linking_function::SYMBOLS.len());
+    }
+
+    // outputs of a generated model are those of the chromosome, bit for bit
+    fn check(k: &str, max_delay: usize, min_changes: usize, mut step: impl FnMut(&[f32]) -> f32) {
+        let rows = rows();
+        let expected = outputs(&chromosome(k), max_delay, &rows);
+        assert!(changes(&expected) > min_changes);
+        for (row, s) in rows.iter().zip(&expected) {
+            assert_eq!(step(row).to_bits(), s.to_bits());
+        }
+    }
+
+    #[test]
+    fn step_test() {
+        let mut m = wsum_model::WSumModel::new();
+        check(WSUM, 3, 100, |row| m.step(row));
+        let mut m = gate_model::GateModel::default();
+        check(GATE, 2, 100, |row| m.step(row));
+        let mut m = maj_model::MajModel::new();
+        check(MAJ, 2, 50, |row| m.step(row));
+        let mut m = mean_model::MeanModel::new();
+        check(MEAN, 2, 100, |row| m.step(row));
+        let mut m = unan_model::UnanModel::new();
+        check(UNAN, 2, 100, |row| m.step(row));
+    }
+
+    // every entry of the code tables, reachable by symbol through function(), state_function() and linker()
+    fn primitives() -> String {
+        let mut definitions = String::new();
+        let mut functions = String::new();
+        for (symbol, body) in FUNCTIONS {
+            let fd = FREGISTRY.iter().chain(EXT_FREGISTRY).find(|fd| fd.symbol == *symbol).unwrap();
+            let params: Vec<String> = (0..fd.arity).map(|i| format!("a{}: f32", i)).collect();
+            let args: Vec<String> = (0..fd.arity).map(|i| format!("a[{}]", i)).collect();
+            definitions.push_str(&format!("\nfn {}({}) -> f32 {{\n    {}\n}}\n", rust_name(symbol), params.join(", "), body));
+            functions.push_str(&format!("        \"{}\" => {}({}),\n", symbol, rust_name(symbol), args.join(", ")));
+        }
+        let mut state_functions = String::new();
+        for (symbol, op) in STATE_FUNCTIONS {
+            definitions.push('\n');
+            definitions.push_str(op);
+            state_functions.push_str(&format!("        \"{}\" => {}_op,\n", symbol, rust_name(symbol)));
+        }
+        let mut linkers = String::new();
+        for (symbol, evaluate, level) in LINKERS {
+            let weighted = crate::linking_function::from_symbol(symbol).unwrap().weighted();
+            let (params, args) = if weighted {("o: &[f32], w: &[f32]", "o, w")} else {("o: &[f32]", "o")};
+            let name = rust_name(symbol);
+            definitions.push_str(&format!("\nfn {}({}) -> f32 {{\n    {}\n}}\n\nfn {}_level({}) -> f32 {{\n    {}\n}}\n",
+                                          name, params, evaluate, name, params, level));
+            linkers.push_str(&format!("        \"{}\" => ({}({}), {}_level({})),\n", symbol, name, args, name, args));
+        }
+        for (helper, code) in HELPERS {
+            if definitions.contains(&format!("{}(", helper)) {
+                definitions.push('\n');
+                definitions.push_str(code);
+            }
+        }
+        format!("// built-in function of a symbol applied to arguments a\n\
+                 pub fn function(symbol: &str, a: &[f32]) -> f32 {{\n    \
+                     match symbol {{\n{functions}        _ => unreachable!(),\n    }}\n\
+                 }}\n\n\
+                 // outputs of a built-in state function of window length `window` for a series of inputs\n\
+                 pub fn state_function(symbol: &str, window: usize, inputs: &[f32]) -> Vec<f32> {{\n    \
+                     let op: fn(f32, &DelayLine, f32) -> f32 = match symbol {{\n{state_functions}        _ => unreachable!(),\n    }};\n    \
+                     let mut m = Memory::new(window);\n    \
+                     inputs.iter().map(|x| m.step(*x, op)).collect()\n\
+                 }}\n\n\
+                 // evaluate and level of a built-in linking function with outputs o and weights w\n\
+                 pub fn linker(symbol: &str, o: &[f32], w: &[f32]) -> (f32, f32) {{\n    \
+                     match symbol {{\n{linkers}        _ => unreachable!(),\n    }}\n\
+                 }}\n{definitions}\n{MEMORY}")
+    }
+
+    // table entries compute what the library computes, bit for bit, on a grid of arguments
+    #[test]
+    fn primitives_test() {
+        if std::env::var_os("GEN_PRIMITIVES").is_some() { std::fs::write("testdata/primitives.rs", primitives()).unwrap() }
+        assert_eq!(primitives(), include_str!("../testdata/primitives.rs"), "testdata/primitives.rs is out of date, regenerate it with GEN_PRIMITIVES=1");
+        let grid = [-2.0, -1.0, -0.75, -0.5, -0.1, 0.0, 0.25, 0.5, 0.9, 1.0, 1.5];
+        for fd in FREGISTRY.iter().chain(EXT_FREGISTRY) {
+            let n = fd.arity as u32;
+            for k in 0..grid.len().pow(n) {
+                let a: Vec<f32> = (0..n).map(|i| grid[k / grid.len().pow(i) % grid.len()]).collect();
+                assert_eq!(primitives::function(fd.symbol, &a).to_bits(), (fd.op)(&a).to_bits(), "{} {:?}", fd.symbol, a);
+            }
+        }
+        let mut rng = StdRng::seed_from_u64(7);
+        let inputs: Vec<f32> = (0..200).map(|_| rng.gen_range(-1.5..1.5)).collect();
+        for sfd in SFREGISTRY.iter().chain(EXT_SFREGISTRY) {
+            for window in if sfd.windowed {vec![1, 2, 3, 5, 20]} else {vec![1]} {
+                let f = StateFunction::with_window(sfd, window);
+                let mut m = f.memory();
+                let expected: Vec<u32> = inputs.iter().map(|x| f.eval(*x, &mut m).to_bits()).collect();
+                let got: Vec<u32> = primitives::state_function(sfd.symbol, window, &inputs).iter().map(|x| x.to_bits()).collect();
+                assert_eq!(got, expected, "{}{}", sfd.symbol, window);
+            }
+        }
+        let weights = [0.6, -0.3, 0.0];
+        for symbol in crate::linking_function::SYMBOLS {
+            let lf = crate::linking_function::from_symbol(symbol).unwrap();
+            for n in 1..=3u32 {
+                for k in 0..grid.len().pow(n) {
+                    let o: Vec<f32> = (0..n).map(|i| grid[k / grid.len().pow(i) % grid.len()]).collect();
+                    let w = if lf.weighted() {&weights[..n as usize]} else {&[][..]};
+                    let (evaluate, level) = primitives::linker(symbol, &o, w);
+                    assert_eq!(evaluate.to_bits(), lf.evaluate(&o, w).to_bits(), "{} {:?}", symbol, o);
+                    assert_eq!(level.to_bits(), lf.level(&o, w).to_bits(), "{} level {:?}", symbol, o);
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::delay_line::DelayLine;
 use crate::feedback::Feedback;
 use crate::functions::Function;
-use crate::state_functions::StateFunction;
+use crate::state_functions::{Memory, StateFunction};
 use crate::terminal::Terminal;
 
 pub enum Codon {
@@ -9,15 +9,18 @@ pub enum Codon {
     StateFunction(StateFunction),
     Terminal(Terminal),
     Feedback(Feedback),
+    // random numerical constant `?`, its value comes from the Dc domain of the gene
+    Constant,
 }
 
 impl Clone for Codon {
     fn clone(&self) -> Self {
         match self {
-            Codon::Function(ref f) => Codon::Function(Function::new(f.fd)),
-            Codon::StateFunction(ref f) => Codon::StateFunction(StateFunction::new(f.fd)),
+            Codon::Function(ref f) => Codon::Function(Function::new(f.fd.clone())),
+            Codon::StateFunction(ref f) => Codon::StateFunction(StateFunction::with_window(f.fd, f.window)),
             Codon::Terminal(ref t) => Codon::Terminal(Terminal::new(t.i)),
-            Codon::Feedback((ref f)) => Codon::Feedback(Feedback::new(f.i))
+            Codon::Feedback(ref f) => Codon::Feedback(Feedback::new(f.i)),
+            Codon::Constant => Codon::Constant,
         }
     }
 }
@@ -25,16 +28,26 @@ impl Clone for Codon {
 impl Codon{
     pub fn get_symbol(&self) -> &str {
         match self{
-            Codon::Function(ref f) => f.fd.symbol,
-            Codon::StateFunction(ref f) => f.fd.symbol,
+            Codon::Function(ref f) => &f.fd.symbol,
+            Codon::StateFunction(ref f) => &f.symbol,
             Codon::Terminal(ref t) => &*t.symbol,
-            Codon::Feedback(ref f) => &*f.symbol
+            Codon::Feedback(ref f) => &*f.symbol,
+            Codon::Constant => "?",
+        }
+    }
+    // k-string token: index of an input, "z(i)" for feedback, symbol otherwise
+    pub fn token(&self) -> String {
+        match self {
+            Codon::Terminal(ref t) => t.i.to_string(),
+            Codon::Feedback(ref f) => format!("z({})", f.i),
+            _ => self.get_symbol().to_string(),
         }
     }
     pub fn is_terminal(&self) -> bool {
         match self{
             Codon::Terminal(ref _t) => true,
             Codon::Feedback(ref _t) => true,
+            Codon::Constant => true,
             _ => false
         }
     }
@@ -43,37 +56,27 @@ impl Codon{
             Codon::Function(ref f) => f.fd.arity,
             Codon::Terminal(ref _t) => 0,
             Codon::Feedback(ref _t) => 0,
+            Codon::Constant => 0,
             _ => 1
         }
 
     }
-    pub fn set_first_arg_position(&self, pos:usize) {
-        match self{
-            Codon::Function(ref f) => f.first_arg_position.set(pos),
-            Codon::StateFunction(ref f) => f.first_arg_position.set(pos),
-            _ => { },
-        }
-    }
-    pub fn get_first_arg_position(&self) -> usize{
-        match self{
-            Codon::Function(ref f) => f.first_arg_position.get(),
-            Codon::StateFunction(ref f) => f.first_arg_position.get(),
-            _ => 0,
-        }
-    }
-    pub fn evaluate(&self, x: f32, y: f32, args: &Vec<f32>, delay_line: &DelayLine) -> f32 {
+    // xs are the arguments (one per arity), mem is the memory of a state function;
+    // both are ignored by terminals; constants are resolved by Program, here they are zero
+    pub fn evaluate(&self, xs: &[f32], args: &Vec<f32>, delay_line: &DelayLine, mem: &mut Memory) -> f32 {
         match self{
-            Codon::Function(ref f) => (f.fd.op)(x,y),
-            Codon::StateFunction(ref f) => f.eval(x),
+            Codon::Function(ref f) => (f.fd.op)(xs),
+            Codon::StateFunction(ref f) => f.eval(xs[0], mem),
             Codon::Terminal(ref t) => t.eval(args),
-            Codon::Feedback(ref f) => f.eval(delay_line)
+            Codon::Feedback(ref f) => f.eval(delay_line),
+            Codon::Constant => 0.0,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::functions::FREGISTRY;
+    use crate::functions::{FREGISTRY, Primitive};
     use crate::state_functions::SFREGISTRY;
     use super::*;
 
@@ -81,22 +84,23 @@ mod tests {
     fn terminal_evaluate_test() {
         let mut t = Codon::Terminal(Terminal::new(1));
         let mut dl = DelayLine::new(3);
-        assert_eq!(t.evaluate(100.0,100.0,&vec![1.0,-1.0,0.0],&dl), -1.0);
+        assert_eq!(t.evaluate(&[],&vec![1.0,-1.0,0.0],&dl, &mut Memory::new(1)), -1.0);
     }
 
     #[test]
     fn function_evaluate_test() {
-        let mut f = Codon::Function(Function::new(&(FREGISTRY[0])));
+        let mut f = Codon::Function(Function::new(Primitive::builtin(&FREGISTRY[0])));
         let mut dl = DelayLine::new(3);
-        assert_eq!(f.evaluate(-1.0,1.0,&vec![-1.0,1.0,0.0], &dl), -1.0);
+        assert_eq!(f.evaluate(&[-1.0,1.0],&vec![-1.0,1.0,0.0], &dl, &mut Memory::new(1)), -1.0);
     }
 
     #[test]
     fn delay_evaluate_test() {
         let mut f = Codon::StateFunction(StateFunction::new(&SFREGISTRY[0]));
         let mut dl = DelayLine::new(3);
-        assert_eq!(f.evaluate(-1.0,1.0,&vec![-1.0,1.0,0.0], &dl), 0.0);
-        assert_eq!(f.evaluate(1.0,1.0,&vec![-1.0,1.0,0.0]), -1.0);
+        let mut buf = Memory::new(1);
+        assert_eq!(f.evaluate(&[-1.0],&vec![-1.0,1.0,0.0], &dl, &mut buf), 0.0);
+        assert_eq!(f.evaluate(&[1.0],&vec![-1.0,1.0,0.0], &dl, &mut buf), -1.0);
     }
 
     #[test]
@@ -106,7 +110,7 @@ mod tests {
         dl.push(0.0);
         dl.push(1.0);
         dl.push(2.0);
-        assert_eq!(f.evaluate(-1.0,1.0,&vec![-1.0,1.0,0.0], &dl), 0.0);
+        assert_eq!(t.evaluate(&[],&vec![-1.0,1.0,0.0], &dl, &mut Memory::new(1)), 1.0);
     }
 }
 
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use serde::Deserialize;
 
 #[derive(Deserialize)]
@@ -11,5 +12,77 @@ pub struct Config {
     pub passes: usize,
     pub train_fraction: f32,
     pub max_delay: usize,
-    pub data_path: String
+    pub data_path: String,
+    // optional RNG seed; the same seed and data reproduce the same run
+    pub seed: Option<u64>,
+    // evaluation threads (needs the "parallel" feature): 0 - all cores, 1 or none - sequential
+    pub threads: Option<usize>,
+    // symbols of built-in functions and state functions to use; none - the basic set
+    pub functions: Option<Vec<String>>,
+    // window lengths for windowed state functions (Sma, Ema, ...); none - 2, 3, 5, 10, 20
+    pub windows: Option<Vec<usize>>,
+    // random numerical constants per gene and their range; none - no constants, [-1, 1]
+    pub constants: Option<usize>,
+    pub constant_range: Option<[f32; 2]>,
+    // probabilities of Dc transposition and constant mutation; none - 0
+    pub dc_transposition_probability: Option<f32>,
+    pub constant_mutation_probability: Option<f32>,
+    // Maj, WSum, Mean, Unan or Gate; none - Maj
+    pub linking_function: Option<String>,
+    // Roulette, Tour<k>, Rank<pressure> or Trunc<fraction>, e.g. "Tour3"; none - Roulette
+    pub selection: Option<String>,
+    // homeotic genes combining the genes (ADFs) instead of the linking function alone,
+    // and their head length; none - 0, head_length
+    pub homeotic_genes: Option<usize>,
+    pub homeotic_head_length: Option<usize>,
+    // best chromosomes copied unchanged to the next generation; none - 1
+    pub elite_count: Option<usize>,
+    // probabilities of further GEP operators; none - 0
+    pub is_transposition_probability: Option<f32>,
+    pub gene_transposition_probability: Option<f32>,
+    pub inversion_probability: Option<f32>,
+    pub one_point_crossover_probability: Option<f32>,
+    pub gene_recombination_probability: Option<f32>,
+    // two-point crossover cut points: Free, Genes (gene boundaries) or InGene; none - Free
+    pub crossover: Option<String>,
+    // probability of two-point crossover of a pair; none - 1
+    pub crossover_probability: Option<f32>,
+    // rate schedules by operator (mutation, transposition, crossover, is_transposition, ...):
+    // Const, Lin<final rate>, Exp<factor>, Stag<factor> or Fifth<factor>; none - fixed rates
+    pub schedules: Option<BTreeMap<String, String>>,
+    // formats of the expression of the best chromosome saved with the results: Text, Dot
+    // (Graphviz) or Rust; none - Text
+    pub expression_formats: Option<Vec<String>>,
+    // passes between checkpoints of the search and their file; none - no checkpoints, ./checkpoint.toml
+    pub checkpoint_every: Option<usize>,
+    pub checkpoint_path: Option<String>,
+    // checkpoint to resume the search from for `passes` more passes, the search is set up by
+    // the config saved with it; none - new search
+    pub resume: Option<String>,
+    // continuous position in [-1,1] instead of -1/0/1; none - false
+    pub continuous_position: Option<bool>,
+    // continuous positions are rounded to multiples of 1/levels; none or 0 - not rounded
+    pub position_levels: Option<usize>,
+    // cost per unit of position change: commission + slippage + proportional_slippage * |price change|;
+    // none - 0
+    pub commission: Option<f32>,
+    pub slippage: Option<f32>,
+    pub proportional_slippage: Option<f32>,
+    // Equity, Sharpe, Sortino, Calmar, PF, Hit, LogG or Comp; none - Equity
+    pub objective: Option<String>,
+    // weights of objectives in Comp, e.g. { Sharpe = 1.0, Hit = 0.5 }
+    pub objective_weights: Option<BTreeMap<String, f32>>,
+    // multi-objective trading search: Return, Drawdown, Turnover, Size and any objective above,
+    // e.g. ["Return", "Drawdown", "Size"]; none - single objective search
+    pub pareto: Option<Vec<String>>,
+    // the search stops once max. fitness exceeds it; none - 0.95 in trading mode, all passes otherwise
+    pub target_fitness: Option<f32>,
+    // trading, regression or classification; none - trading
+    pub mode: Option<String>,
+    // regression: MSE, RMSE or R2 (none - MSE); classification: Acc, F1 or Hit (none - Acc)
+    pub metric: Option<String>,
+    // classification: number of classes, targets are 0..classes; none - 2
+    pub classes: Option<usize>,
+    // classification: max. distance of output to the value of the target class for Hit; none - 0.5
+    pub hit_threshold: Option<f32>,
 }
@@ -13,6 +13,12 @@ impl DelayLine{
         }
         DelayLine{capacity:c,idx:0,buff}
     }
+    pub fn reset(&mut self){
+        for v in self.buff.iter_mut(){
+            *v = 0.0
+        }
+        self.idx = 0;
+    }
     pub fn push(&mut self, v:f32){
         self.buff[self.idx] = v;
         self.idx += 1;
@@ -42,4 +48,18 @@ mod tests {
         assert_eq!(dl.get_shifted_back(2), 3.0);
         assert_eq!(dl.get_shifted_back(3), 2.0);
     }
+
+    #[test]
+    fn reset_test() {
+        let mut dl = DelayLine::new(2);
+        dl.push(1.0);
+        dl.push(2.0);
+        dl.push(3.0);
+        dl.reset();
+        assert_eq!(dl.get_shifted_back(0), 0.0);
+        assert_eq!(dl.get_shifted_back(1), 0.0);
+        dl.push(4.0);
+        assert_eq!(dl.get_shifted_back(0), 4.0);
+        assert_eq!(dl.get_shifted_back(1), 0.0);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,393 @@
+use crate::codons::Codon;
+use crate::linking_function::LinkingFunction;
+
+// how Expression::render writes an expression
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    // formula in prefix notation, e.g. Maj(Max(args[0], Neg(Delay(args[2]))), Min(args[1], 0.25))
+    Text,
+    // Graphviz digraph of the expression trees
+    Dot,
+    // Rust function of the inputs, previous outputs (z) and memories of state functions, every
+    // function and state function is a call of the function named by its lowercase symbol,
+    // state functions get their memory as the last argument
+    Rust,
+}
+
+impl Format {
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "Text" => Some(Format::Text),
+            "Dot" => Some(Format::Dot),
+            "Rust" => Some(Format::Rust),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Text => "txt",
+            Format::Dot => "dot",
+            Format::Rust => "rs",
+        }
+    }
+}
+
+// expressed codon with its arguments
+struct Node<'a> {
+    codon: &'a Codon,
+    // value of a random numerical constant, 0 otherwise
+    value: f32,
+    args: Vec<Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    // tree of a gene in Karva notation, values are those of its `?` codons in order of position
+    fn decode(gene: &'a [Codon], values: &[f32]) -> Self {
+        // position of the first argument of every expressed codon
+        let mut first_arg_positions = Vec::with_capacity(gene.len());
+        let mut first_arg_position = 1;
+        let mut pos = 0;
+        while pos < first_arg_position {
+            first_arg_positions.push(first_arg_position);
+            first_arg_position += usize::from(gene[pos].get_arity());
+            pos += 1;
+        }
+        Self::build(gene, 0, &first_arg_positions, values)
+    }
+
+    fn build(gene: &'a [Codon], pos: usize, first_arg_positions: &[usize], values: &[f32]) -> Self {
+        let codon = &gene[pos];
+        let value = match codon {
+            Codon::Constant => {
+                let k = gene[..pos].iter().filter(|c| matches!(c, Codon::Constant)).count();
+                values.get(k).cloned().unwrap_or(0.0)
+            }
+            _ => 0.0,
+        };
+        let args = (0..usize::from(codon.get_arity()))
+            .map(|i| Self::build(gene, first_arg_positions[pos] + i, first_arg_positions, values))
+            .collect();
+        Node { codon, value, args }
+    }
+
+    // terminals of homeotic genes (adf) are outputs of genes
+    fn label(&self, adf: bool) -> String {
+        match self.codon {
+            Codon::Terminal(ref t) if adf => format!("adf{}", t.i),
+            Codon::Constant => self.value.to_string(),
+            c => c.get_symbol().to_string(),
+        }
+    }
+
+    fn text(&self, adf: bool) -> String {
+        if self.args.is_empty() { return self.label(adf) }
+        let args: Vec<String> = self.args.iter().map(|a| a.text(adf)).collect();
+        format!("{}({})", self.label(adf), args.join(", "))
+    }
+
+    // memories are numbered in order of evaluation (postfix) like in Program, windows holds
+    // the window of every memory
+    fn rust(&self, adf: bool, windows: &mut Vec<usize>) -> String {
+        let args: Vec<String> = self.args.iter().map(|a| a.rust(adf, windows)).collect();
+        match self.codon {
+            Codon::Terminal(ref t) => if adf {format!("adf{}", t.i)} else {format!("args[{}]", t.i)},
+            Codon::Feedback(ref f) => format!("z[{}]", f.i),
+            Codon::Constant => format!("{:?}", self.value),
+            Codon::Function(ref f) => format!("{}({})", rust_name(&f.fd.symbol), args.join(", ")),
+            // memory comes last, so nested state functions borrow their memories one after another
+            Codon::StateFunction(ref f) => {
+                windows.push(f.window);
+                format!("{}({}, &mut memory[{}])", rust_name(&f.symbol), args[0], windows.len() - 1)
+            }
+        }
+    }
+
+    fn collect(&self, codons: &mut Vec<&'a Codon>) {
+        codons.push(self.codon);
+        for a in &self.args {
+            a.collect(codons);
+        }
+    }
+
+    // largest index of an input (not of a homeotic terminal) and of a previous output plus one
+    fn reads(&self, adf: bool, inputs: &mut usize, feedback: &mut usize) {
+        match self.codon {
+            Codon::Terminal(ref t) if !adf => *inputs = (*inputs).max(t.i + 1),
+            Codon::Feedback(ref f) => *feedback = (*feedback).max(f.i + 1),
+            _ => {}
+        }
+        for a in &self.args {
+            a.reads(adf, inputs, feedback);
+        }
+    }
+
+    // nodes and edges of the tree, returns the id of its root; terminals of homeotic genes are
+    // the roots of genes (ids in adfs)
+    fn dot(&self, out: &mut String, next: &mut usize, adfs: &[usize]) -> usize {
+        if let (Codon::Terminal(ref t), false) = (self.codon, adfs.is_empty()) { return adfs[t.i] }
+        let id = *next;
+        *next += 1;
+        out.push_str(&format!("    n{} [label=\"{}\"];\n", id, self.label(false)));
+        for a in &self.args {
+            let child = a.dot(out, next, adfs);
+            out.push_str(&format!("    n{} -> n{};\n", id, child));
+        }
+        id
+    }
+}
+
+// lowercase symbol as a Rust identifier: "Sma10" -> sma10, "Maj~4" -> maj_c4, "If" -> if_
+pub(crate) fn rust_name(symbol: &str) -> String {
+    let name = symbol.to_lowercase().replace('~', "_c");
+    if name == "if" {"if_".to_string()} else {name}
+}
+
+// expressed part of a chromosome: trees of genes and homeotic genes and the linking function
+pub struct Expression<'a> {
+    genes: Vec<Node<'a>>,
+    homeotic: Vec<Node<'a>>,
+    pub(crate) linker: &'a dyn LinkingFunction,
+    pub(crate) weights: &'a [f32],
+}
+
+impl<'a> Expression<'a> {
+    // constants[g] holds values of the `?` codons of gene g in order of position (see Program)
+    pub fn new(codons: &'a [Codon], gene_length: usize, nbr_of_genes: usize, linker: &'a dyn LinkingFunction,
+               weights: &'a [f32], constants: &[Vec<f32>]) -> Self {
+        let genes = (0..nbr_of_genes).map(|i| {
+            let values = constants.get(i).map(|c| c.as_slice()).unwrap_or(&[]);
+            Node::decode(&codons[i * gene_length..(i + 1) * gene_length], values)
+        }).collect();
+        Expression { genes, homeotic: Vec::new(), linker, weights }
+    }
+
+    // homeotic genes, terminal i of a homeotic gene is the output of gene i
+    pub fn with_homeotic(mut self, codons: &'a [Codon], gene_length: usize, nbr_of_genes: usize) -> Self {
+        self.homeotic = (0..nbr_of_genes)
+            .map(|i| Node::decode(&codons[i * gene_length..(i + 1) * gene_length], &[]))
+            .collect();
+        self
+    }
+
+    // expressed codons of genes and homeotic genes
+    pub(crate) fn codons(&self) -> Vec<&'a Codon> {
+        let mut codons = Vec::new();
+        for n in self.genes.iter().chain(&self.homeotic) {
+            n.collect(&mut codons);
+        }
+        codons
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.text(),
+            Format::Dot => self.dot(),
+            Format::Rust => self.rust(),
+        }
+    }
+
+    // "WSum[0.5, -0.2]" for weighted linking functions
+    fn linker_label(&self) -> String {
+        if self.weights.is_empty() { return self.linker.symbol() }
+        let w: Vec<String> = self.weights.iter().map(|w| w.to_string()).collect();
+        format!("{}[{}]", self.linker.symbol(), w.join(", "))
+    }
+
+    // with homeotic genes the genes are defined first, one per line
+    fn text(&self) -> String {
+        let mut lines = Vec::new();
+        let outputs: Vec<String> = if self.homeotic.is_empty() {
+            self.genes.iter().map(|g| g.text(false)).collect()
+        } else {
+            for (i, g) in self.genes.iter().enumerate() {
+                lines.push(format!("adf{} = {}", i, g.text(false)));
+            }
+            self.homeotic.iter().map(|h| h.text(true)).collect()
+        };
+        lines.push(format!("{}({})", self.linker_label(), outputs.join(", ")));
+        lines.join("\n")
+    }
+
+    fn dot(&self) -> String {
+        let mut out = String::from("digraph model {\n");
+        out.push_str(&format!("    n0 [label=\"{}\", shape=box];\n", self.linker.symbol()));
+        let mut next = 1;
+        let genes: Vec<usize> = self.genes.iter().map(|g| g.dot(&mut out, &mut next, &[])).collect();
+        let outputs: Vec<usize> = if self.homeotic.is_empty() {genes} else {
+            self.homeotic.iter().map(|h| h.dot(&mut out, &mut next, &genes)).collect()
+        };
+        for (i, o) in outputs.iter().enumerate() {
+            match self.weights.get(i) {
+                Some(w) => out.push_str(&format!("    n0 -> n{} [label=\"{}\"];\n", o, w)),
+                None => out.push_str(&format!("    n0 -> n{};\n", o)),
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn rust(&self) -> String {
+        self.rust_function().0
+    }
+
+    // model function of Format::Rust and the window of every memory it uses; inputs and previous
+    // outputs are clipped to [-1,1] like terminals and feedback do, outputs of genes as well when
+    // homeotic genes read them
+    pub(crate) fn rust_function(&self) -> (String, Vec<usize>) {
+        let mut windows = Vec::new();
+        let (mut inputs, mut feedback) = (0, 0);
+        for (n, adf) in self.genes.iter().map(|g| (g, false)).chain(self.homeotic.iter().map(|h| (h, true))) {
+            n.reads(adf, &mut inputs, &mut feedback);
+        }
+        let mut body = String::new();
+        if inputs > 0 {
+            body.push_str(&format!("    let args: [f32; {}] = std::array::from_fn(|i| args[i].clamp(-1.0, 1.0));\n", inputs));
+        }
+        if feedback > 0 {
+            body.push_str(&format!("    let z: [f32; {}] = std::array::from_fn(|i| z[i].clamp(-1.0, 1.0));\n", feedback));
+        }
+        for (i, g) in self.genes.iter().enumerate() {
+            let code = g.rust(false, &mut windows);
+            if self.homeotic.is_empty() {
+                body.push_str(&format!("    let g{} = {};\n", i, code));
+            } else {
+                body.push_str(&format!("    let adf{} = f32::clamp({}, -1.0, 1.0);\n", i, code));
+            }
+        }
+        let gene = if self.homeotic.is_empty() {"g"} else {"adf"};
+        let mut outputs: Vec<String> = (0..self.genes.len()).map(|i| format!("{}{}", gene, i)).collect();
+        if !self.homeotic.is_empty() {
+            for (i, h) in self.homeotic.iter().enumerate() {
+                body.push_str(&format!("    let h{} = {};\n", i, h.rust(true, &mut windows)));
+            }
+            outputs = (0..self.homeotic.len()).map(|i| format!("h{}", i)).collect();
+        }
+        let linker = rust_name(&self.linker.symbol());
+        let link = if self.weights.is_empty() {
+            format!("{}(&[{}])", linker, outputs.join(", "))
+        } else {
+            let w: Vec<String> = self.weights.iter().map(|w| format!("{:?}", w)).collect();
+            format!("{}(&[{}], &[{}])", linker, outputs.join(", "), w.join(", "))
+        };
+        (format!("// memory holds {} memories of state functions\n#[allow(unused_variables)]\n\
+                  fn model(args: &[f32], z: &[f32], memory: &mut [Memory]) -> f32 {{\n{}    {}\n}}\n",
+                 windows.len(), body, link), windows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chromosome::Chromosome;
+    use crate::function_set::FunctionSet;
+
+    const K: &str = "Maj |Max| |0| |Neg| |Delay| |2| |1| |0| |1| |1| Dc(0,0,0,0,0) C(0.1,0.2) \
+                     // |Min| |?| |Ma2| |1| |0| |?| |1| |0| |1| Dc(1,0,0,0,0) C(-0.5,0.25)";
+
+    fn render(k: &str, format: Format) -> String {
+        Chromosome::from_k_string(k, &FunctionSet::default()).unwrap().expression().render(format)
+    }
+
+    #[test]
+    fn text_test() {
+        assert_eq!(render(K, Format::Text), "Maj(Max(args[0], Neg(Delay(args[2]))), Min(0.25, Ma2(args[1])))");
+        let k = "WSum(0.5,-0.25) |Avg| |z(1)| |Ma2| |0| |1| |1| |0| |1| |1| // |Neg| |0| |1| |1| |1| |1| |0| |1| |1|";
+        assert_eq!(render(k, Format::Text), "WSum[0.5, -0.25](Avg(z[1], Ma2(args[0])), Neg(args[0]))");
+        let k = "Gate |Max| |0| |1| |1| |1| |1| |0| |1| |1| // |Neg| |0| |1| |1| |1| |1| |0| |1| |1| ## |Avg| |1| |0|";
+        assert_eq!(render(k, Format::Text), "adf0 = Max(args[0], args[1])\nadf1 = Neg(args[0])\nGate(Avg(adf1, adf0))");
+    }
+
+    #[test]
+    fn rust_test() {
+        assert_eq!(render(K, Format::Rust), "// memory holds 2 memories of state functions\n\
+            #[allow(unused_variables)]\n\
+            fn model(args: &[f32], z: &[f32], memory: &mut [Memory]) -> f32 {\n    \
+            let args: [f32; 3] = std::array::from_fn(|i| args[i].clamp(-1.0, 1.0));\n    \
+            let g0 = max(args[0], neg(delay(args[2], &mut memory[0])));\n    \
+            let g1 = min(0.25, ma2(args[1], &mut memory[1]));\n    \
+            maj(&[g0, g1])\n}\n");
+        let k = "Mean~4 |Diff| |Ma2| |0| |1| |1| |1| |0| |1| |1| ## |Avg| |0| |0| // |Diff| |0| |0|";
+        let rust = render(k, Format::Rust);
+        assert!(rust.contains("let adf0 = f32::clamp(diff(ma2(args[0], &mut memory[0]), &mut memory[1]), -1.0, 1.0);"));
+        assert!(rust.contains("let h1 = diff(adf0, &mut memory[2]);"));
+        assert!(rust.contains("    mean_c4(&[h0, h1])\n"));
+        assert_eq!(rust_name("If"), "if_");
+    }
+
+    #[test]
+    fn dot_test() {
+        let dot = render(K, Format::Dot);
+        assert!(dot.starts_with("digraph model {\n    n0 [label=\"Maj\", shape=box];\n"));
+        assert_eq!(dot.matches("[label=").count(), 10);
+        assert_eq!(dot.matches(" -> ").count(), 9);
+        assert!(dot.contains("n0 -> n1;") && dot.contains("n0 -> n6;"));
+        // homeotic terminals point to the genes
+        let k = "WSum(0.5) |Max| |0| |1| |1| |1| |1| |0| |1| |1| // |Neg| |0| |1| |1| |1| |1| |0| |1| |1| ## |Avg| |1| |1|";
+        let dot = render(k, Format::Dot);
+        assert_eq!(dot.matches("n6 -> n4;").count(), 2);
+        assert!(dot.contains("n0 -> n6 [label=\"0.5\"];"));
+        assert_eq!(Format::from_symbol("Dot"), Some(Format::Dot));
+        assert_eq!(Format::from_symbol("Png"), None);
+    }
+
+    // homeotic genes, nested state functions, feedback and values beyond [-1,1]
+    const RENDERED: &str = "Mean~4 |Sma3| |Diff| |Add| |0| |z(1)| |1| |2| |0| |1| |1| Dc(0,0,0,0,0,0,0) C(0.5,0.25) \
+        // |Mul| |?| |Ma2| |2| |0| |?| |1| |0| |1| |1| Dc(1,0,0,0,0,0,0) C(0.5,1.375) \
+        ## |Diff| |Avg| |0| |1| |0| |1| |0| // |Max| |0| |1| |1| |0| |0| |0|";
+
+    // the rendered model compiled with the functions it calls evaluated by the library
+    mod rendered {
+        use rand::{Rng, SeedableRng};
+        use rand::rngs::StdRng;
+        use crate::chromosome::Chromosome;
+        use crate::codons::Codon;
+        use crate::delay_line::DelayLine;
+        use crate::expression::Format;
+        use crate::function_set::FunctionSet;
+        use crate::linking_function::from_symbol;
+        use crate::state_functions::Memory;
+
+        include!("../testdata/rendered_model.rs");
+
+        fn call(symbol: &str, args: &[f32]) -> f32 {
+            match FunctionSet::builtins().codon(symbol) {
+                Some(Codon::Function(f)) => (f.fd.op)(args),
+                _ => panic!("{} is not a function", symbol),
+            }
+        }
+        fn state(symbol: &str, x: f32, m: &mut Memory) -> f32 {
+            match FunctionSet::builtins().codon(symbol) {
+                Some(Codon::StateFunction(f)) => f.eval(x, m),
+                _ => panic!("{} is not a state function", symbol),
+            }
+        }
+        fn add(a0: f32, a1: f32) -> f32 { call("Add", &[a0, a1]) }
+        fn mul(a0: f32, a1: f32) -> f32 { call("Mul", &[a0, a1]) }
+        fn avg(a0: f32, a1: f32) -> f32 { call("Avg", &[a0, a1]) }
+        fn max(a0: f32, a1: f32) -> f32 { call("Max", &[a0, a1]) }
+        fn sma3(x: f32, m: &mut Memory) -> f32 { state("Sma3", x, m) }
+        fn diff(x: f32, m: &mut Memory) -> f32 { state("Diff", x, m) }
+        fn ma2(x: f32, m: &mut Memory) -> f32 { state("Ma2", x, m) }
+        fn mean_c4(o: &[f32]) -> f32 { from_symbol("Mean~4").unwrap().evaluate(o, &[]) }
+
+        #[test]
+        fn rendered_test() {
+            let mut fs = FunctionSet::builtins();
+            fs.set_constants(2, -1.5, 1.5);
+            let c = Chromosome::from_k_string(super::RENDERED, &fs).unwrap();
+            assert_eq!(c.expression().render(Format::Rust), include_str!("../testdata/rendered_model.rs"));
+            let mut program = c.translate();
+            let mut memory = vec![Memory::new(1), Memory::new(3), Memory::new(1), Memory::new(1)];
+            let mut dl = DelayLine::new(2);
+            let mut rng = StdRng::seed_from_u64(7);
+            for _ in 0..200 {
+                let row: Vec<f32> = (0..3).map(|_| rng.gen_range(-1.5..1.5)).collect();
+                let z: Vec<f32> = (0..2).map(|i| dl.get_shifted_back(i)).collect();
+                let s = program.eval(&row, &dl);
+                assert_eq!(model(&row, &z, &mut memory).to_bits(), s.to_bits());
+                dl.push(s);
+            }
+        }
+    }
+}
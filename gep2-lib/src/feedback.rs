@@ -8,7 +8,7 @@ impl Feedback{
     pub fn new(i:usize) -> Self{
         Feedback{
             i,
-            symbol: format!("delay[{}]", i)
+            symbol: format!("z[{}]", i)
         }
     }
     pub fn eval(&self, delay_line: &DelayLine) -> f32 {
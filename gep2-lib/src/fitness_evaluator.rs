@@ -1,17 +1,46 @@
 use std::f32;
 use crate::data_table::DataTable;
 use crate::delay_line::DelayLine;
+use crate::program::Program;
 
-pub trait FitnessEvaluator {
-    fn evaluate<'a>(&self, max_delay:usize, func: Box<dyn Fn(&Vec<f32>, &DelayLine) -> f32 +'a>) -> f32;
-    fn equity<'a>(&self, max_delay: usize, train:bool, func: Box<dyn Fn(&Vec<f32>, &DelayLine) -> f32 +'a>) -> Vec<f32>;
+// Sync: the population may score chromosomes from several threads
+pub trait FitnessEvaluator: Sync {
+    fn evaluate(&self, max_delay:usize, program: &mut Program) -> f32;
+    fn equity(&self, max_delay: usize, train:bool, program: &mut Program) -> Vec<f32>;
+    // chromosome output (position) for every row of the train or test data
+    fn outputs(&self, max_delay: usize, train:bool, program: &mut Program) -> Vec<f32>;
+}
+
+// cost per unit of position change (turnover): commission + slippage + proportional_slippage * |row result|
+#[derive(Clone, Copy, Default)]
+pub struct Costs {
+    pub commission: f32,
+    pub slippage: f32,
+    pub proportional_slippage: f32,
+}
+
+impl Costs {
+    // negative values are treated as zero
+    pub fn new(commission: f32, slippage: f32, proportional_slippage: f32) -> Self {
+        Costs {
+            commission: commission.max(0.0),
+            slippage: slippage.max(0.0),
+            proportional_slippage: proportional_slippage.max(0.0),
+        }
+    }
+
+    // cost of changing position by `turnover` on a row with result `r`
+    pub fn of(&self, turnover: f32, r: f32) -> f32 {
+        turnover * (self.commission + self.slippage + self.proportional_slippage * r.abs())
+    }
 }
 
 pub struct FitnessFunction{
     data_table:DataTable,
     end_of_train_data:usize,
     train_perfect_equity:f32,
-    test_perfect_equity: f32
+    test_perfect_equity: f32,
+    costs: Costs
 }
 
 impl FitnessFunction{
@@ -28,45 +57,155 @@ impl FitnessFunction{
         }
         let data_table = DataTable::new(a,r).unwrap();
         FitnessFunction{data_table,end_of_train_data,
-            train_perfect_equity: train_e, test_perfect_equity: test_e}
+            train_perfect_equity: train_e, test_perfect_equity: test_e, costs: Costs::default()}
+    }
+
+    pub fn set_costs(&mut self, costs: Costs) {
+        self.costs = costs;
+    }
+
+    // sum of absolute row results: equity of perfect positions without costs
+    pub fn perfect_equity(&self, train: bool) -> f32 {
+        if train {self.train_perfect_equity} else {self.test_perfect_equity}
+    }
+
+    // net result (after costs) of every row of the train or test data
+    pub fn returns(&self, max_delay: usize, train: bool, program: &mut Program) -> Vec<f32> {
+        let mut ret = Vec::new();
+        self.run(max_delay, train, program, |_, r| ret.push(r));
+        ret
+    }
+
+    // position and net result of every row
+    pub fn trades(&self, max_delay: usize, train: bool, program: &mut Program) -> Vec<(f32, f32)> {
+        let mut ret = Vec::new();
+        self.run(max_delay, train, program, |s, r| ret.push((s, r)));
+        ret
+    }
+
+    fn rows(&self, train: bool) -> std::ops::Range<usize> {
+        if train {0..self.end_of_train_data} else {self.end_of_train_data..self.data_table.rows}
+    }
+
+    // runs the program over train or test rows, calls f with position and net result of every row;
+    // the position is flat before the first row
+    fn run(&self, max_delay: usize, train: bool, program: &mut Program, mut f: impl FnMut(f32, f32)) {
+        let mut dl = DelayLine::new(max_delay);
+        let mut prev = 0.0f32;
+        program.reset();
+        for i in self.rows(train) {
+            let row = self.data_table.get_data_row(i).unwrap();
+            let s = program.eval(row.0, &dl);
+            f(s, s*row.1 - self.costs.of((s - prev).abs(), row.1));
+            prev = s;
+            dl.push(s);
+        }
     }
 }
 impl FitnessEvaluator for FitnessFunction {
-    fn evaluate<'a>(&self, max_delay:usize, func: Box<dyn Fn(&Vec<f32>, &DelayLine) -> f32 +'a>) -> f32 {
+    // equity after costs, normalized by the equity of perfect positions without costs
+    fn evaluate(&self, max_delay:usize, program: &mut Program) -> f32 {
         let mut res = 0.0f32;
-        let mut dl = DelayLine::new(max_delay);
-        for i in 0..self.end_of_train_data {
-            let row = self.data_table.get_data_row(i).unwrap();
-            let s =  func(row.0,&dl);
-            res += row.1 * s;
-            dl.push(s)
-        };
+        self.run(max_delay, true, program, |_, r| res += r);
         if res > 0.0 {res/self.train_perfect_equity} else {0.0}
     }
 
-    fn equity<'a>(&self, max_delay: usize, train: bool, func: Box<dyn Fn(&Vec<f32>, &DelayLine) -> f32 + 'a>) -> Vec<f32> {
+    // positions may be fractional, a row earns position * result less costs
+    fn equity(&self, max_delay: usize, train: bool, program: &mut Program) -> Vec<f32> {
         let mut ret = Vec::new();
         let mut res = 0.0f32;
-        let mut dl = DelayLine::new(max_delay);
-        if train{
-            for i in 0..self.end_of_train_data {
-                let row = self.data_table.get_data_row(i).unwrap();
-                let s = func(row.0, &dl);
-                res += s*row.1;
-                dl.push(s);
-                ret.push(res);
-            }
-        }
-        else{
-            for i in self.end_of_train_data..self.data_table.rows {
-                let row = self.data_table.get_data_row(i).unwrap();
-                let s = func(row.0, &dl);
-                res += s*row.1;
-                dl.push(s);
-                ret.push(res);
-            }
-        }
+        self.run(max_delay, train, program, |_, r| {
+            res += r;
+            ret.push(res);
+        });
+        ret
+    }
+
+    fn outputs(&self, max_delay: usize, train: bool, program: &mut Program) -> Vec<f32> {
+        let mut ret = Vec::new();
+        self.run(max_delay, train, program, |s, _| ret.push(s));
         ret
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::codons::Codon;
+    use crate::state_functions::{StateFunction, SFREGISTRY};
+    use crate::terminal::Terminal;
+
+    fn sample() -> (FitnessFunction, Vec<Codon>) {
+        // alternating input, the last train row is negative
+        let args = (0..40).map(|i| vec![if i % 2 == 0 {0.5} else {-0.5}]).collect();
+        let results = (0..40).map(|i| 0.1 * (i % 3) as f32 + 0.1).collect();
+        // Delay(args[0]): stale memory changes the first output of a run
+        let codons = vec![
+            Codon::StateFunction(StateFunction::new(&SFREGISTRY[0])),
+            Codon::Terminal(Terminal::new(0)),
+            Codon::Terminal(Terminal::new(0)),
+        ];
+        (FitnessFunction::new(args, results, 0.5), codons)
+    }
+
+    #[test]
+    fn evaluation_is_repeatable_test() {
+        let (ff, codons) = sample();
+        let mut p = Program::new(&codons, 3, 1);
+        let f1 = ff.evaluate(2, &mut p);
+        let f2 = ff.evaluate(2, &mut p);
+        assert_eq!(f1, f2);
+    }
+
+    #[test]
+    fn equity_does_not_depend_on_call_order_test() {
+        let (ff, codons) = sample();
+        let test_first = ff.equity(2, false, &mut Program::new(&codons, 3, 1));
+        let mut p = Program::new(&codons, 3, 1);
+        let train = ff.equity(2, true, &mut p);
+        let test = ff.equity(2, false, &mut p);
+        assert_eq!(test, test_first);
+        assert_eq!(train, ff.equity(2, true, &mut p));
+    }
+
+    #[test]
+    fn fractional_positions_test() {
+        use crate::linking_function::{continuous, Majority};
+        let (ff, codons) = sample();
+        let lf = continuous(Arc::new(Majority), 0);
+        let mut p = Program::with_linker(&codons, 3, 1, lf.as_ref(), &[]);
+        let positions = ff.outputs(2, true, &mut p);
+        assert!(positions.iter().all(|s| s.abs() == 0.5 || *s == 0.0));
+        let equity = ff.equity(2, true, &mut p);
+        let mut res = 0.0;
+        for i in 0..positions.len() {
+            res += positions[i] * ff.data_table.get_data_row(i).unwrap().1;
+            assert_eq!(equity[i], res);
+        }
+        let res = equity.last().unwrap().max(0.0);
+        assert_eq!(ff.evaluate(2, &mut p), res / ff.train_perfect_equity);
+    }
+
+    #[test]
+    fn costs_test() {
+        // result follows the sign of the input: args[0] flips every row and is always right
+        let args: Vec<Vec<f32>> = (0..20).map(|i| vec![if i % 2 == 0 {0.5} else {-0.5}]).collect();
+        let results = args.iter().map(|a| a[0]).collect();
+        let mut ff = FitnessFunction::new(args, results, 1.0);
+        let flip = vec![Codon::Terminal(Terminal::new(0))];
+        assert_eq!(ff.evaluate(1, &mut Program::new(&flip, 1, 1)), 1.0);
+
+        ff.set_costs(Costs::new(0.05, 0.05, 0.2));
+        // the first row turns over 1 unit, every next row 2 units: 20 * 0.5 - 39 * 0.2
+        let flipping = ff.evaluate(1, &mut Program::new(&flip, 1, 1));
+        assert!((flipping - 2.2 / 10.0).abs() < 1e-5);
+        let equity = ff.equity(1, true, &mut Program::new(&flip, 1, 1));
+        assert!((equity[19] - 2.2).abs() < 1e-5);
+        assert!((equity[0] - 0.3).abs() < 1e-6);
+        assert!((equity[1] - 0.4).abs() < 1e-6);
+
+        ff.set_costs(Costs::new(0.2, 0.1, 0.0));
+        assert_eq!(ff.evaluate(1, &mut Program::new(&flip, 1, 1)), 0.0);
+    }
+}
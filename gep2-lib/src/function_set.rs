@@ -0,0 +1,264 @@
+use std::sync::Arc;
+use rand::Rng;
+use crate::codons::Codon;
+use crate::feedback::Feedback;
+use crate::terminal::Terminal;
+use crate::functions::{Function, Primitive, EXT_FREGISTRY, FREGISTRY};
+use crate::state_functions::{StateFunction, StateFunctionDescription, EXT_SFREGISTRY, SFREGISTRY};
+
+// non-terminals available to evolution in a given experiment
+#[derive(Clone)]
+pub struct FunctionSet {
+    functions: Vec<Arc<Primitive>>,
+    state_functions: Vec<&'static StateFunctionDescription>,
+    // window lengths to choose from for windowed state functions
+    windows: Vec<usize>,
+    // random numerical constants per gene (GEP-RNC) and their range; none by default
+    constants: usize,
+    constant_range: (f32, f32),
+}
+
+impl Default for FunctionSet {
+    // basic built-in functions and basic state functions (FREGISTRY and SFREGISTRY)
+    fn default() -> Self {
+        let mut fs = FunctionSet::new();
+        for fd in FREGISTRY {
+            fs.add_builtin(fd.symbol);
+        }
+        for sfd in SFREGISTRY {
+            fs.add_builtin(sfd.symbol);
+        }
+        fs
+    }
+}
+
+impl FunctionSet {
+    // empty set, to be filled with add_builtin/register
+    pub fn new() -> Self {
+        FunctionSet { functions: Vec::new(), state_functions: Vec::new(), windows: vec![2, 3, 5, 10, 20],
+            constants: 0, constant_range: (-1.0, 1.0) }
+    }
+
+    // every built-in function and state function, to resolve any built-in symbol
+    pub fn builtins() -> Self {
+        let mut fs = FunctionSet::new();
+        for fd in FREGISTRY.iter().chain(EXT_FREGISTRY) {
+            fs.add_builtin(fd.symbol);
+        }
+        for sfd in SFREGISTRY.iter().chain(EXT_SFREGISTRY) {
+            fs.add_builtin(sfd.symbol);
+        }
+        fs
+    }
+
+    // built-ins chosen by symbol; None if any symbol is unknown
+    pub fn from_symbols(symbols: &[String]) -> Option<Self> {
+        let mut fs = FunctionSet::new();
+        for s in symbols {
+            if !fs.add_builtin(s) { return None }
+        }
+        Some(fs)
+    }
+
+    // adds a built-in function or state function; false if the symbol is unknown
+    pub fn add_builtin(&mut self, symbol: &str) -> bool {
+        if let Some(fd) = FREGISTRY.iter().chain(EXT_FREGISTRY).find(|fd| fd.symbol == symbol) {
+            self.insert(Primitive::builtin(fd));
+            return true;
+        }
+        if let Some(sfd) = SFREGISTRY.iter().chain(EXT_SFREGISTRY).find(|sfd| sfd.symbol == symbol) {
+            if !self.state_functions.iter().any(|f| f.symbol == symbol) {
+                self.state_functions.push(sfd);
+            }
+            return true;
+        }
+        false
+    }
+
+    // adds a user function, op receives exactly `arity` arguments;
+    // a function with the same symbol is replaced
+    pub fn register(&mut self, symbol: &str, arity: u8, op: impl Fn(&[f32]) -> f32 + Send + Sync + 'static) {
+        assert!(arity > 0, "arity of '{}' must be positive", symbol);
+        self.insert(Arc::new(Primitive::new(symbol, arity, op)));
+    }
+
+    fn insert(&mut self, p: Arc<Primitive>) {
+        match self.functions.iter().position(|f| f.symbol == p.symbol) {
+            Some(i) => self.functions[i] = p,
+            None => self.functions.push(p),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.functions.len() + self.state_functions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // zero-length windows are ignored; an empty list keeps the current windows
+    pub fn set_windows(&mut self, windows: &[usize]) {
+        let w: Vec<usize> = windows.iter().cloned().filter(|w| *w > 0).collect();
+        if !w.is_empty() { self.windows = w }
+    }
+
+    // every gene gets an array of `count` random constants in [lo, hi) and the `?` terminal;
+    // 0 - no constants; bounds are swapped if needed
+    pub fn set_constants(&mut self, count: usize, lo: f32, hi: f32) {
+        self.constants = count;
+        self.constant_range = if lo <= hi {(lo, hi)} else {(hi, lo)};
+    }
+
+    pub fn constants(&self) -> usize {
+        self.constants
+    }
+
+    pub(crate) fn random_constant<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        let (lo, hi) = self.constant_range;
+        if lo < hi {rng.gen_range(lo..hi)} else {lo}
+    }
+
+    // state functions are unary; an empty set still needs a tail of one codon
+    pub fn max_arity(&self) -> u8 {
+        self.functions.iter().map(|f| f.arity).max().unwrap_or(1)
+    }
+
+    pub fn symbols(&self) -> Vec<&str> {
+        self.functions.iter().map(|f| f.symbol.as_str())
+            .chain(self.state_functions.iter().map(|f| f.symbol))
+            .collect()
+    }
+
+    // codon of a k-string token (see Codon::token): input index, "z(i)", "?", a function of this set
+    // or a state function of this set with its window ("Sma10"); None if unknown
+    pub fn codon(&self, token: &str) -> Option<Codon> {
+        if token == "?" {
+            return Some(Codon::Constant);
+        }
+        if let Ok(i) = token.parse::<usize>() {
+            return Some(Codon::Terminal(Terminal::new(i)));
+        }
+        if let Some(i) = token.strip_prefix("z(").and_then(|t| t.strip_suffix(')')) {
+            return Some(Codon::Feedback(Feedback::new(i.parse().ok()?)));
+        }
+        if let Some(f) = self.functions.iter().find(|f| f.symbol == token) {
+            return Some(Codon::Function(Function::new(f.clone())));
+        }
+        if let Some(sfd) = self.state_functions.iter().find(|f| !f.windowed && f.symbol == token) {
+            return Some(Codon::StateFunction(StateFunction::new(sfd)));
+        }
+        self.state_functions.iter().filter(|f| f.windowed).find_map(|sfd| {
+            let window: usize = token.strip_prefix(sfd.symbol)?.parse().ok()?;
+            if window > 0 {Some(Codon::StateFunction(StateFunction::with_window(sfd, window)))} else {None}
+        })
+    }
+
+    // uniformly chosen function or state function; window of a windowed state function
+    // is chosen uniformly from the configured list; panics on an empty set
+    pub(crate) fn random_non_terminal<R: Rng + ?Sized>(&self, rng: &mut R) -> Codon {
+        assert!(!self.is_empty(), "function set is empty: add functions with add_builtin or register");
+        let fl = self.functions.len();
+        let i = rng.gen::<usize>() % self.len();
+        if i < fl { return Codon::Function(Function::new(self.functions[i].clone())) }
+        let sfd = self.state_functions[i - fl];
+        if sfd.windowed {
+            let window = self.windows[rng.gen::<usize>() % self.windows.len()];
+            Codon::StateFunction(StateFunction::with_window(sfd, window))
+        } else {
+            Codon::StateFunction(StateFunction::new(sfd))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha12Rng;
+
+    #[test]
+    fn default_set_test() {
+        let fs = FunctionSet::default();
+        assert_eq!(fs.symbols(), vec!["Min", "Max", "Avg", "Neg", "Delay", "Ma2", "Diff"]);
+    }
+
+    #[test]
+    fn from_symbols_test() {
+        let fs = FunctionSet::from_symbols(&["Max".to_string(), "Diff".to_string()]).unwrap();
+        assert_eq!(fs.symbols(), vec!["Max", "Diff"]);
+        assert!(FunctionSet::from_symbols(&["Foo".to_string()]).is_none());
+        let fs = FunctionSet::from_symbols(&["Mul".to_string(), "Gt".to_string()]).unwrap();
+        assert_eq!(fs.symbols(), vec!["Mul", "Gt"]);
+    }
+
+    #[test]
+    fn max_arity_test() {
+        assert_eq!(FunctionSet::default().max_arity(), 2);
+        assert_eq!(FunctionSet::from_symbols(&["Neg".to_string(), "Delay".to_string()]).unwrap().max_arity(), 1);
+        let mut fs = FunctionSet::default();
+        fs.add_builtin("If");
+        assert_eq!(fs.max_arity(), 3);
+        fs.register("Sum4", 4, |a| a.iter().sum::<f32>() / 4.0);
+        assert_eq!(fs.max_arity(), 4);
+    }
+
+    #[test]
+    fn windowed_test() {
+        let mut fs = FunctionSet::from_symbols(&["Sma".to_string()]).unwrap();
+        fs.set_windows(&[0, 7]);
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        match fs.random_non_terminal(&mut rng) {
+            Codon::StateFunction(f) => assert_eq!(f.symbol, "Sma7"),
+            _ => panic!("state function expected"),
+        }
+    }
+
+    #[test]
+    fn constants_test() {
+        let mut fs = FunctionSet::default();
+        assert_eq!(fs.constants(), 0);
+        fs.set_constants(4, 0.5, -0.5);
+        assert_eq!(fs.constants(), 4);
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        assert!((0..100).all(|_| (-0.5..0.5).contains(&fs.random_constant(&mut rng))));
+        fs.set_constants(1, 0.3, 0.3);
+        assert_eq!(fs.random_constant(&mut rng), 0.3);
+    }
+
+    #[test]
+    fn codon_test() {
+        let mut fs = FunctionSet::default();
+        fs.add_builtin("Sma");
+        fs.register("Mul", 2, |a| a[0] * a[1]);
+        for token in ["3", "z(1)", "?", "Max", "Mul", "Delay", "Sma10"] {
+            assert_eq!(fs.codon(token).unwrap().token(), token);
+        }
+        assert_eq!(fs.codon("Mul").unwrap().get_arity(), 2);
+        for token in ["Foo", "Sma", "Sma0", "Ema3", "z(x)", "Delay2"] {
+            assert!(fs.codon(token).is_none(), "{}", token);
+        }
+        let all = FunctionSet::builtins();
+        assert!(all.codon("Ema3").is_some() && all.codon("If").is_some() && all.codon("Mul").is_some());
+        assert_eq!(all.codon("Mul").unwrap().get_arity(), 2);
+    }
+
+    #[test]
+    fn register_test() {
+        let mut fs = FunctionSet::new();
+        fs.register("Mul", 2, |a| a[0] * a[1]);
+        fs.register("Mul", 2, |a| a[0] * a[1] * 0.5);
+        assert_eq!(fs.len(), 1);
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        match fs.random_non_terminal(&mut rng) {
+            Codon::Function(f) => assert_eq!((f.fd.op)(&[1.0, -1.0]), -0.5),
+            _ => panic!("function expected"),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "function set is empty")]
+    fn empty_set_test() {
+        FunctionSet::new().random_non_terminal(&mut ChaCha12Rng::seed_from_u64(1));
+    }
+}
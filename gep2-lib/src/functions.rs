@@ -1,27 +1,35 @@
-use std::cell::Cell;
+use std::sync::Arc;
 
+// description of a built-in function
 pub struct FunctionDescription {
     pub symbol : &'static str,
     pub arity: u8,
-    pub op : fn(f32, f32) -> f32
+    // receives exactly `arity` arguments
+    pub op : fn(&[f32]) -> f32
 }
 
-pub fn av(x: f32, y: f32) -> f32{
-    (x+y)/2.0
+pub fn mi(a: &[f32]) -> f32{
+    a[0].min(a[1])
 }
-pub fn ne(x: f32, _y: f32) -> f32{
-    -x
+pub fn ma(a: &[f32]) -> f32{
+    a[0].max(a[1])
+}
+pub fn av(a: &[f32]) -> f32{
+    (a[0]+a[1])/2.0
+}
+pub fn ne(a: &[f32]) -> f32{
+    -a[0]
 }
 
 pub const FREGISTRY: &'static [FunctionDescription] = &[
     FunctionDescription {
         arity: 2,
-        op: f32::min,
+        op: mi,
         symbol: "Min"
     },
     FunctionDescription {
         arity: 2,
-        op: f32::max,
+        op: ma,
         symbol: "Max"
     },
     FunctionDescription {
@@ -38,14 +46,148 @@ pub const FREGISTRY: &'static [FunctionDescription] = &[
 
 pub const FN_NUM: usize = 4;
 
+fn clip(x: f32) -> f32{
+    x.clamp(-1.0, 1.0)
+}
+// fuzzy logic on [-1,1]: values are mapped to truth degrees in [0,1] and back (product t-norm)
+fn to_truth(x: f32) -> f32{ 0.5*(x + 1.0) }
+fn from_truth(t: f32) -> f32{ 2.0*t - 1.0 }
+
+fn sign(x: f32) -> f32{
+    if x > 0.0 {1.0} else if x < 0.0 {-1.0} else {0.0}
+}
+
+pub fn sg(a: &[f32]) -> f32{
+    sign(a[0])
+}
+// sign of x if it exceeds the threshold |y|, otherwise 0
+pub fn th(a: &[f32]) -> f32{
+    if a[0].abs() > a[1].abs() {sign(a[0])} else {0.0}
+}
+pub fn mu(a: &[f32]) -> f32{
+    a[0]*a[1]
+}
+pub fn ad(a: &[f32]) -> f32{
+    clip(a[0]+a[1])
+}
+pub fn su(a: &[f32]) -> f32{
+    clip(a[0]-a[1])
+}
+pub fn ab(a: &[f32]) -> f32{
+    a[0].abs()
+}
+pub fn and(a: &[f32]) -> f32{
+    from_truth(to_truth(a[0])*to_truth(a[1]))
+}
+pub fn or(a: &[f32]) -> f32{
+    let (x, y) = (to_truth(a[0]), to_truth(a[1]));
+    from_truth(x + y - x*y)
+}
+pub fn gt(a: &[f32]) -> f32{
+    if a[0] > a[1] {1.0} else {-1.0}
+}
+pub fn lt(a: &[f32]) -> f32{
+    if a[0] < a[1] {1.0} else {-1.0}
+}
+// if-then-else: y if x is positive, otherwise z
+pub fn ite(a: &[f32]) -> f32{
+    if a[0] > 0.0 {a[1]} else {a[2]}
+}
+pub fn med3(a: &[f32]) -> f32{
+    a[0].max(a[1]).min(a[0].min(a[1]).max(a[2]))
+}
+
+// additional built-ins, not used unless selected explicitly
+pub const EXT_FREGISTRY: &'static [FunctionDescription] = &[
+    FunctionDescription {
+        arity: 1,
+        op: sg,
+        symbol: "Sgn"
+    },
+    FunctionDescription {
+        arity: 2,
+        op: th,
+        symbol: "Thr"
+    },
+    FunctionDescription {
+        arity: 2,
+        op: mu,
+        symbol: "Mul"
+    },
+    FunctionDescription {
+        arity: 2,
+        op: ad,
+        symbol: "Add"
+    },
+    FunctionDescription {
+        arity: 2,
+        op: su,
+        symbol: "Sub"
+    },
+    FunctionDescription {
+        arity: 1,
+        op: ab,
+        symbol: "Abs"
+    },
+    FunctionDescription {
+        arity: 2,
+        op: and,
+        symbol: "And"
+    },
+    FunctionDescription {
+        arity: 2,
+        op: or,
+        symbol: "Or"
+    },
+    FunctionDescription {
+        arity: 2,
+        op: gt,
+        symbol: "Gt"
+    },
+    FunctionDescription {
+        arity: 2,
+        op: lt,
+        symbol: "Lt"
+    },
+    FunctionDescription {
+        arity: 3,
+        op: ite,
+        symbol: "If"
+    },
+    FunctionDescription {
+        arity: 3,
+        op: med3,
+        symbol: "Med3"
+    },
+];
+
+// operation of a primitive, shared across threads
+pub type PrimitiveOp = Box<dyn Fn(&[f32]) -> f32 + Send + Sync>;
+
+// function as used by chromosomes: a built-in or a user-registered one
+pub struct Primitive {
+    pub symbol: String,
+    pub arity: u8,
+    pub op: PrimitiveOp,
+    // false for user functions, even if they replace a built-in symbol
+    pub builtin: bool,
+}
+impl Primitive {
+    pub fn new(symbol: &str, arity: u8, op: impl Fn(&[f32]) -> f32 + Send + Sync + 'static) -> Self {
+        Primitive { symbol: symbol.to_string(), arity, op: Box::new(op), builtin: false }
+    }
+    pub fn builtin(fd: &FunctionDescription) -> Arc<Self> {
+        Arc::new(Primitive { builtin: true, ..Self::new(fd.symbol, fd.arity, fd.op) })
+    }
+}
+
 pub struct Function{
-    pub fd: &'static FunctionDescription,
-    // to hide mutability
-    pub first_arg_position:Cell<usize>
+    // shared by all codons referring to the same primitive
+    pub fd: Arc<Primitive>,
 }
 impl Function{
-    pub fn new(fd: &'static FunctionDescription) -> Self{
-        Function{ fd, first_arg_position:Cell::new(0) }
+    pub fn new(fd: Arc<Primitive>) -> Self{
+        Function{ fd }
     }
 }
 
@@ -59,15 +201,83 @@ mod tests {
     }
     #[test]
     fn registry_mi_test() {
-        assert_eq!((FREGISTRY[0].op)(1.0, -1.0), -1.0);
+        assert_eq!((FREGISTRY[0].op)(&[1.0, -1.0]), -1.0);
     }
     #[test]
     fn registry_ne_test() {
-        assert_eq!((FREGISTRY[FN_NUM-1].op)(1.0, 1.0), -1.0);
+        assert_eq!((FREGISTRY[FN_NUM-1].op)(&[1.0]), -1.0);
     }
     #[test]
     fn registry_av_test() {
-        assert_eq!((FREGISTRY[FN_NUM-2].op)(1.0, -1.0), 0.0);
+        assert_eq!((FREGISTRY[FN_NUM-2].op)(&[1.0, -1.0]), 0.0);
+    }
+
+    fn ext(symbol: &str) -> fn(&[f32]) -> f32 {
+        EXT_FREGISTRY.iter().find(|fd| fd.symbol == symbol).unwrap().op
+    }
+    #[test]
+    fn ext_registry_symbols_test() {
+        for fd in EXT_FREGISTRY {
+            assert!(FREGISTRY.iter().all(|f| f.symbol != fd.symbol));
+            assert_eq!(EXT_FREGISTRY.iter().filter(|f| f.symbol == fd.symbol).count(), 1);
+        }
+    }
+    #[test]
+    fn sgn_test() {
+        assert_eq!(ext("Sgn")(&[0.3]), 1.0);
+        assert_eq!(ext("Sgn")(&[-0.3]), -1.0);
+        assert_eq!(ext("Sgn")(&[0.0]), 0.0);
+    }
+    #[test]
+    fn thr_test() {
+        assert_eq!(ext("Thr")(&[0.5, 0.2]), 1.0);
+        assert_eq!(ext("Thr")(&[-0.5, -0.2]), -1.0);
+        assert_eq!(ext("Thr")(&[0.1, -0.2]), 0.0);
+    }
+    #[test]
+    fn mul_test() {
+        assert_eq!(ext("Mul")(&[0.5, -0.5]), -0.25);
+    }
+    #[test]
+    fn add_sub_test() {
+        assert_eq!(ext("Add")(&[0.25, 0.5]), 0.75);
+        assert_eq!(ext("Add")(&[0.75, 0.5]), 1.0);
+        assert_eq!(ext("Sub")(&[0.25, 0.5]), -0.25);
+        assert_eq!(ext("Sub")(&[-0.75, 0.5]), -1.0);
+    }
+    #[test]
+    fn abs_test() {
+        assert_eq!(ext("Abs")(&[-0.5]), 0.5);
+    }
+    #[test]
+    fn fuzzy_test() {
+        // full truth/falsity behave like boolean logic
+        assert_eq!(ext("And")(&[1.0, 1.0]), 1.0);
+        assert_eq!(ext("And")(&[1.0, -1.0]), -1.0);
+        assert_eq!(ext("Or")(&[1.0, -1.0]), 1.0);
+        assert_eq!(ext("Or")(&[-1.0, -1.0]), -1.0);
+        // 0 is "half true"
+        assert_eq!(ext("And")(&[0.0, 0.0]), -0.5);
+        assert_eq!(ext("Or")(&[0.0, 0.0]), 0.5);
+    }
+    #[test]
+    fn if_test() {
+        assert_eq!(ext("If")(&[0.5, 0.2, -0.3]), 0.2);
+        assert_eq!(ext("If")(&[0.0, 0.2, -0.3]), -0.3);
+    }
+    #[test]
+    fn med3_test() {
+        assert_eq!(ext("Med3")(&[0.5, 0.2, -0.3]), 0.2);
+        assert_eq!(ext("Med3")(&[-0.3, 0.5, 0.2]), 0.2);
+        assert_eq!(ext("Med3")(&[0.2, -0.3, 0.5]), 0.2);
+        assert_eq!(ext("Med3")(&[0.5, 0.5, -0.3]), 0.5);
+    }
+    #[test]
+    fn compare_test() {
+        assert_eq!(ext("Gt")(&[0.5, 0.2]), 1.0);
+        assert_eq!(ext("Gt")(&[0.2, 0.2]), -1.0);
+        assert_eq!(ext("Lt")(&[0.1, 0.2]), 1.0);
+        assert_eq!(ext("Lt")(&[0.5, 0.2]), -1.0);
     }
 
 }
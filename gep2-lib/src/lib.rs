@@ -1,11 +1,20 @@
 #![allow(dead_code)]
 mod functions;
+pub mod function_set;
 mod state_functions;
 mod terminal;
 mod codons;
 pub mod chromosome;
-mod linking_function;
+pub mod program;
+pub mod expression;
+mod codegen;
+pub mod linking_function;
 pub mod fitness_evaluator;
+pub mod objectives;
+pub mod supervised;
+pub mod pareto;
+pub mod selection;
+pub mod schedule;
 mod data_table;
 pub mod population;
 pub mod statistics;
@@ -1,11 +1,210 @@
-pub struct LinkingFunction {
-    pub symbol : &'static str,
+use std::sync::Arc;
+
+// combines outputs of genes into the output of a chromosome
+pub trait LinkingFunction: Send + Sync {
+    fn symbol(&self) -> String;
+    // weights (one per gene) are given to weighted linking functions only
+    fn evaluate(&self, outputs: &[f32], weights: &[f32]) -> f32;
+    // continuous counterpart of evaluate, in [-1,1] for gene outputs in [-1,1]
+    fn level(&self, outputs: &[f32], weights: &[f32]) -> f32 {
+        self.evaluate(outputs, weights)
+    }
+    // true if the linking function uses evolvable per-gene weights
+    fn weighted(&self) -> bool { false }
+}
+
+fn sign(x: f32) -> f32 {
+    if x > 0.0 {1.0} else if x < 0.0 {-1.0} else {0.0}
+}
+
+fn mean(x: &[f32]) -> f32 {
+    x.iter().sum::<f32>() / x.len() as f32
+}
+
+// majority vote (a tie counts as long)
+pub struct Majority;
+impl LinkingFunction for Majority {
+    fn symbol(&self) -> String { "Maj".to_string() }
+    fn evaluate(&self, outputs: &[f32], _weights: &[f32]) -> f32 {
+        outputs.iter().sum::<f32>().signum()
+    }
+    fn level(&self, outputs: &[f32], _weights: &[f32]) -> f32 {
+        mean(outputs)
+    }
+}
+
+// sign of the weighted sum of genes
+pub struct WeightedSum;
+impl LinkingFunction for WeightedSum {
+    fn symbol(&self) -> String { "WSum".to_string() }
+    fn evaluate(&self, outputs: &[f32], weights: &[f32]) -> f32 {
+        sign(outputs.iter().zip(weights).map(|(o, w)| o * w).sum::<f32>())
+    }
+    fn level(&self, outputs: &[f32], weights: &[f32]) -> f32 {
+        let norm = weights.iter().map(|w| w.abs()).sum::<f32>();
+        if norm > 0.0 {outputs.iter().zip(weights).map(|(o, w)| o * w).sum::<f32>() / norm} else {0.0}
+    }
+    fn weighted(&self) -> bool { true }
 }
 
-impl LinkingFunction {
-    pub fn evaluate(&self, args: Vec<f32>) -> f32{
-        args.iter().sum::<f32>().signum()
+// average of genes: continuous position size
+pub struct Average;
+impl LinkingFunction for Average {
+    fn symbol(&self) -> String { "Mean".to_string() }
+    fn evaluate(&self, outputs: &[f32], _weights: &[f32]) -> f32 {
+        mean(outputs)
+    }
+}
+
+// position only if all genes agree
+pub struct Unanimous;
+impl LinkingFunction for Unanimous {
+    fn symbol(&self) -> String { "Unan".to_string() }
+    fn evaluate(&self, outputs: &[f32], _weights: &[f32]) -> f32 {
+        if outputs.iter().all(|o| *o > 0.0) {1.0}
+        else if outputs.iter().all(|o| *o < 0.0) {-1.0}
+        else {0.0}
+    }
+    fn level(&self, outputs: &[f32], weights: &[f32]) -> f32 {
+        if self.evaluate(outputs, weights) != 0.0 {mean(outputs)} else {0.0}
+    }
+}
+
+// first gene opens the gate (positive output), the rest vote
+pub struct FirstGated;
+impl LinkingFunction for FirstGated {
+    fn symbol(&self) -> String { "Gate".to_string() }
+    fn evaluate(&self, outputs: &[f32], _weights: &[f32]) -> f32 {
+        if outputs.len() < 2 { return sign(outputs[0]) }
+        if outputs[0] > 0.0 { sign(outputs[1..].iter().sum::<f32>()) } else {0.0}
+    }
+    fn level(&self, outputs: &[f32], _weights: &[f32]) -> f32 {
+        if outputs.len() < 2 { return outputs[0] }
+        if outputs[0] > 0.0 { mean(&outputs[1..]) } else {0.0}
+    }
+}
+
+// continuous position: level of the inner linking function clipped to [-1,1] and,
+// if levels > 0, rounded to multiples of 1/levels (e.g. 4 -> steps of 0.25)
+pub struct Continuous {
+    pub inner: Arc<dyn LinkingFunction>,
+    pub levels: usize,
+}
+impl LinkingFunction for Continuous {
+    fn symbol(&self) -> String {
+        if self.levels > 0 {format!("{}~{}", self.inner.symbol(), self.levels)} else {format!("{}~", self.inner.symbol())}
+    }
+    fn evaluate(&self, outputs: &[f32], weights: &[f32]) -> f32 {
+        let x = self.inner.level(outputs, weights).clamp(-1.0, 1.0);
+        if self.levels > 0 { (x * self.levels as f32).round() / self.levels as f32 } else {x}
     }
+    fn weighted(&self) -> bool { self.inner.weighted() }
 }
 
-pub const LF: LinkingFunction = LinkingFunction { symbol: "Maj" };
\ No newline at end of file
+pub fn continuous(inner: Arc<dyn LinkingFunction>, levels: usize) -> Arc<dyn LinkingFunction> {
+    Arc::new(Continuous { inner, levels })
+}
+
+// symbols of the built-in linking functions
+pub const SYMBOLS: &[&str] = &["Maj", "WSum", "Mean", "Unan", "Gate"];
+
+// "Maj", or "Maj~"/"Maj~4" for continuous positions
+pub fn from_symbol(symbol: &str) -> Option<Arc<dyn LinkingFunction>> {
+    if let Some((inner, levels)) = symbol.split_once('~') {
+        let levels = if levels.is_empty() {0} else {levels.parse().ok()?};
+        return Some(continuous(from_symbol(inner)?, levels));
+    }
+    match symbol {
+        "Maj" => Some(Arc::new(Majority)),
+        "WSum" => Some(Arc::new(WeightedSum)),
+        "Mean" => Some(Arc::new(Average)),
+        "Unan" => Some(Arc::new(Unanimous)),
+        "Gate" => Some(Arc::new(FirstGated)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols_test() {
+        for s in SYMBOLS {
+            assert_eq!(from_symbol(s).unwrap().symbol(), *s);
+        }
+    }
+
+    #[test]
+    fn majority_test() {
+        assert_eq!(Majority.evaluate(&[1.0, -0.5, -0.2], &[]), 1.0);
+        assert_eq!(Majority.evaluate(&[1.0, -0.5, -0.7], &[]), -1.0);
+        assert_eq!(Majority.evaluate(&[0.5, -0.5], &[]), 1.0);
+    }
+
+    #[test]
+    fn weighted_sum_test() {
+        assert_eq!(WeightedSum.evaluate(&[1.0, -0.5], &[0.2, 0.5]), -1.0);
+        assert_eq!(WeightedSum.evaluate(&[1.0, -0.5], &[0.2, -0.5]), 1.0);
+        assert_eq!(WeightedSum.evaluate(&[1.0, 0.5], &[0.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn average_test() {
+        assert_eq!(Average.evaluate(&[1.0, -0.5, 0.0, 0.5], &[]), 0.25);
+    }
+
+    #[test]
+    fn unanimous_test() {
+        assert_eq!(Unanimous.evaluate(&[1.0, 0.5, 0.2], &[]), 1.0);
+        assert_eq!(Unanimous.evaluate(&[-1.0, -0.5], &[]), -1.0);
+        assert_eq!(Unanimous.evaluate(&[1.0, 0.0], &[]), 0.0);
+    }
+
+    #[test]
+    fn first_gated_test() {
+        assert_eq!(FirstGated.evaluate(&[0.5, -0.5, -0.2], &[]), -1.0);
+        assert_eq!(FirstGated.evaluate(&[-0.5, -0.5, -0.2], &[]), 0.0);
+        assert_eq!(FirstGated.evaluate(&[-0.5], &[]), -1.0);
+    }
+
+    #[test]
+    fn from_symbol_test() {
+        for s in ["Maj", "WSum", "Mean", "Unan", "Gate"] {
+            assert_eq!(from_symbol(s).unwrap().symbol(), s);
+        }
+        assert!(from_symbol("Foo").is_none());
+        assert_eq!(from_symbol("WSum~").unwrap().symbol(), "WSum~");
+        assert_eq!(from_symbol("Maj~4").unwrap().symbol(), "Maj~4");
+        assert!(from_symbol("Maj~x").is_none());
+        assert!(from_symbol("Maj~4").unwrap().evaluate(&[0.3, 0.2], &[]) == 0.25);
+    }
+
+    #[test]
+    fn continuous_test() {
+        let c = continuous(Arc::new(Majority), 0);
+        assert_eq!(c.evaluate(&[1.0, -0.5, 0.0, 0.5], &[]), 0.25);
+        let c = continuous(Arc::new(WeightedSum), 0);
+        assert!(c.weighted());
+        assert_eq!(c.evaluate(&[1.0, -0.5], &[0.5, -0.5]), 0.75);
+        assert_eq!(c.evaluate(&[1.0, -0.5], &[0.0, 0.0]), 0.0);
+        let c = continuous(Arc::new(Unanimous), 0);
+        assert_eq!(c.evaluate(&[0.5, 0.25], &[]), 0.375);
+        assert_eq!(c.evaluate(&[0.5, -0.25], &[]), 0.0);
+        let c = continuous(Arc::new(FirstGated), 0);
+        assert_eq!(c.evaluate(&[0.5, 0.25, -0.75], &[]), -0.25);
+        assert_eq!(c.evaluate(&[-0.5, 0.25, -0.75], &[]), 0.0);
+    }
+
+    #[test]
+    fn quantized_test() {
+        let c = continuous(Arc::new(Average), 4);
+        assert_eq!(c.evaluate(&[0.3, 0.3], &[]), 0.25);
+        assert_eq!(c.evaluate(&[0.4, 0.4], &[]), 0.5);
+        assert_eq!(c.evaluate(&[-0.1, 0.0], &[]), 0.0);
+        assert_eq!(c.evaluate(&[-1.0, -0.9], &[]), -1.0);
+        let c = continuous(Arc::new(Average), 1);
+        assert_eq!(c.evaluate(&[0.6, 0.6], &[]), 1.0);
+        assert_eq!(c.evaluate(&[0.4, 0.4], &[]), 0.0);
+    }
+}
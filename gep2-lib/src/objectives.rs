@@ -0,0 +1,242 @@
+use crate::fitness_evaluator::{FitnessEvaluator, FitnessFunction};
+use crate::program::Program;
+
+// scores net per-row returns of a trading model; scores are in [0,1], higher is better,
+// so they work with roulette selection and the early stop of the search
+pub trait Objective: Send + Sync {
+    fn symbol(&self) -> &'static str;
+    // perfect_equity is the sum of absolute row results, the best equity without costs
+    fn score(&self, returns: &[f32], perfect_equity: f32) -> f32;
+}
+
+// maps a non-negative ratio to [0,1); infinity (no risk, positive return) is 1
+fn squash(x: f32) -> f32 {
+    if x.is_infinite() {1.0} else if x > 0.0 {x / (1.0 + x)} else {0.0}
+}
+
+fn mean(x: &[f32]) -> f32 {
+    x.iter().sum::<f32>() / x.len() as f32
+}
+
+// ratio of gain to risk: infinite without risk, zero without gain
+fn ratio(gain: f32, risk: f32) -> f32 {
+    if gain <= 0.0 {0.0} else if risk == 0.0 {f32::INFINITY} else {gain / risk}
+}
+
+// final equity divided by the perfect equity, as FitnessFunction scores it
+pub struct Equity;
+impl Objective for Equity {
+    fn symbol(&self) -> &'static str { "Equity" }
+    fn score(&self, returns: &[f32], perfect_equity: f32) -> f32 {
+        let res = returns.iter().sum::<f32>();
+        if res > 0.0 && perfect_equity > 0.0 {res / perfect_equity} else {0.0}
+    }
+}
+
+// mean return per row over its standard deviation
+pub struct Sharpe;
+impl Objective for Sharpe {
+    fn symbol(&self) -> &'static str { "Sharpe" }
+    fn score(&self, returns: &[f32], _perfect_equity: f32) -> f32 {
+        if returns.len() < 2 { return 0.0 }
+        let m = mean(returns);
+        let var = returns.iter().map(|r| (r - m) * (r - m)).sum::<f32>() / returns.len() as f32;
+        squash(ratio(m, var.sqrt()))
+    }
+}
+
+// mean return per row over the downside deviation
+pub struct Sortino;
+impl Objective for Sortino {
+    fn symbol(&self) -> &'static str { "Sortino" }
+    fn score(&self, returns: &[f32], _perfect_equity: f32) -> f32 {
+        if returns.is_empty() { return 0.0 }
+        let dd = returns.iter().map(|r| r.min(0.0) * r.min(0.0)).sum::<f32>() / returns.len() as f32;
+        squash(ratio(mean(returns), dd.sqrt()))
+    }
+}
+
+// final equity over the maximum drawdown of the equity curve
+pub struct Calmar;
+impl Objective for Calmar {
+    fn symbol(&self) -> &'static str { "Calmar" }
+    fn score(&self, returns: &[f32], _perfect_equity: f32) -> f32 {
+        let mut equity = 0.0f32;
+        let mut peak = 0.0f32;
+        let mut max_dd = 0.0f32;
+        for r in returns {
+            equity += r;
+            peak = peak.max(equity);
+            max_dd = max_dd.max(peak - equity);
+        }
+        squash(ratio(equity, max_dd))
+    }
+}
+
+// gross win over gross loss; a profit factor of 1 or less scores zero
+pub struct ProfitFactor;
+impl Objective for ProfitFactor {
+    fn symbol(&self) -> &'static str { "PF" }
+    fn score(&self, returns: &[f32], _perfect_equity: f32) -> f32 {
+        let win = returns.iter().filter(|r| **r > 0.0).sum::<f32>();
+        let loss = -returns.iter().filter(|r| **r < 0.0).sum::<f32>();
+        squash(ratio(win - loss, loss))
+    }
+}
+
+// fraction of profitable rows among rows with a non-zero result
+pub struct HitRate;
+impl Objective for HitRate {
+    fn symbol(&self) -> &'static str { "Hit" }
+    fn score(&self, returns: &[f32], _perfect_equity: f32) -> f32 {
+        let cnt = returns.iter().filter(|r| **r != 0.0).count();
+        if cnt == 0 { return 0.0 }
+        returns.iter().filter(|r| **r > 0.0).count() as f32 / cnt as f32
+    }
+}
+
+// total log growth of capital, row results are fractions of capital (percentage-change data);
+// a loss of the whole capital scores zero
+pub struct LogGrowth;
+impl Objective for LogGrowth {
+    fn symbol(&self) -> &'static str { "LogG" }
+    fn score(&self, returns: &[f32], _perfect_equity: f32) -> f32 {
+        if returns.iter().any(|r| *r <= -1.0) { return 0.0 }
+        squash(returns.iter().map(|r| r.ln_1p()).sum::<f32>())
+    }
+}
+
+// weighted average of objectives; negative weights are treated as zero
+pub struct Composite {
+    parts: Vec<(f32, Box<dyn Objective>)>,
+}
+impl Composite {
+    pub fn new(parts: Vec<(f32, Box<dyn Objective>)>) -> Self {
+        Composite { parts: parts.into_iter().map(|(w, o)| (w.max(0.0), o)).collect() }
+    }
+}
+impl Objective for Composite {
+    fn symbol(&self) -> &'static str { "Comp" }
+    fn score(&self, returns: &[f32], perfect_equity: f32) -> f32 {
+        let total = self.parts.iter().map(|(w, _)| w).sum::<f32>();
+        if total == 0.0 { return 0.0 }
+        self.parts.iter().map(|(w, o)| w * o.score(returns, perfect_equity)).sum::<f32>() / total
+    }
+}
+
+pub fn from_symbol(symbol: &str) -> Option<Box<dyn Objective>> {
+    match symbol {
+        "Equity" => Some(Box::new(Equity)),
+        "Sharpe" => Some(Box::new(Sharpe)),
+        "Sortino" => Some(Box::new(Sortino)),
+        "Calmar" => Some(Box::new(Calmar)),
+        "PF" => Some(Box::new(ProfitFactor)),
+        "Hit" => Some(Box::new(HitRate)),
+        "LogG" => Some(Box::new(LogGrowth)),
+        _ => None,
+    }
+}
+
+// composite of objectives given by symbol and weight; None if any symbol is unknown
+pub fn composite(weights: &[(String, f32)]) -> Option<Box<dyn Objective>> {
+    let mut parts = Vec::with_capacity(weights.len());
+    for (s, w) in weights {
+        parts.push((*w, from_symbol(s)?));
+    }
+    Some(Box::new(Composite::new(parts)))
+}
+
+// trading model scored by an objective over net returns of the train data
+pub struct ObjectiveEvaluator {
+    pub market: FitnessFunction,
+    pub objective: Box<dyn Objective>,
+}
+
+impl ObjectiveEvaluator {
+    pub fn new(market: FitnessFunction, objective: Box<dyn Objective>) -> Self {
+        ObjectiveEvaluator { market, objective }
+    }
+}
+
+impl FitnessEvaluator for ObjectiveEvaluator {
+    fn evaluate(&self, max_delay: usize, program: &mut Program) -> f32 {
+        let returns = self.market.returns(max_delay, true, program);
+        self.objective.score(&returns, self.market.perfect_equity(true))
+    }
+
+    fn equity(&self, max_delay: usize, train: bool, program: &mut Program) -> Vec<f32> {
+        self.market.equity(max_delay, train, program)
+    }
+
+    fn outputs(&self, max_delay: usize, train: bool, program: &mut Program) -> Vec<f32> {
+        self.market.outputs(max_delay, train, program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codons::Codon;
+    use crate::terminal::Terminal;
+
+    const STEADY: [f32; 4] = [0.1, 0.1, 0.1, 0.1];
+    const MIXED: [f32; 4] = [0.4, -0.1, 0.3, -0.2];
+
+    #[test]
+    fn ratios_test() {
+        assert_eq!(Sharpe.score(&STEADY, 1.0), 1.0);
+        // mean 0.1, variance 0.065
+        assert!((Sharpe.score(&MIXED, 1.0) - squash(0.1 / 0.065f32.sqrt())).abs() < 1e-6);
+        assert_eq!(Sharpe.score(&[-0.1, -0.3], 1.0), 0.0);
+        // downside deviation sqrt(0.05 / 4)
+        let dd = (0.05f32 / 4.0).sqrt();
+        assert!((Sortino.score(&MIXED, 1.0) - squash(0.1 / dd)).abs() < 1e-6);
+        // equity 0.4, drawdown 0.2
+        assert!((Calmar.score(&MIXED, 1.0) - 2.0 / 3.0).abs() < 1e-6);
+        // profit factor 7/3
+        assert!((ProfitFactor.score(&MIXED, 1.0) - (4.0 / 3.0) / (7.0 / 3.0)).abs() < 1e-6);
+        assert_eq!(ProfitFactor.score(&[0.1, -0.2], 1.0), 0.0);
+    }
+
+    #[test]
+    fn rates_test() {
+        assert!((Equity.score(&MIXED, 0.8) - 0.5).abs() < 1e-6);
+        assert_eq!(HitRate.score(&[0.4, 0.0, -0.1, 0.3], 1.0), 2.0 / 3.0);
+        assert!((LogGrowth.score(&[0.1, 0.1], 1.0) - squash(2.0 * 1.1f32.ln())).abs() < 1e-6);
+        assert_eq!(LogGrowth.score(&[0.1, -1.0], 1.0), 0.0);
+        assert_eq!(LogGrowth.score(&[-0.1, -0.1], 1.0), 0.0);
+    }
+
+    #[test]
+    fn scores_are_bounded_test() {
+        let all = ["Equity", "Sharpe", "Sortino", "Calmar", "PF", "Hit", "LogG"];
+        for returns in [&STEADY[..], &MIXED[..], &[-0.5, -0.2][..], &[][..]] {
+            for s in all {
+                let o = from_symbol(s).unwrap();
+                assert_eq!(o.symbol(), s);
+                let v = o.score(returns, 1.0);
+                assert!((0.0..=1.0).contains(&v), "{} {}", s, v);
+            }
+        }
+    }
+
+    #[test]
+    fn composite_test() {
+        let c = composite(&[("Hit".to_string(), 3.0), ("Equity".to_string(), 1.0), ("PF".to_string(), -1.0)]).unwrap();
+        assert_eq!(c.score(&[0.4, -0.1, 0.3, 0.2], 1.6), (3.0 * 0.75 + 0.5) / 4.0);
+        assert!(composite(&[("Foo".to_string(), 1.0)]).is_none());
+    }
+
+    #[test]
+    fn evaluator_test() {
+        let args: Vec<Vec<f32>> = (0..20).map(|i| vec![if i % 3 == 0 {-0.5} else {0.5}]).collect();
+        let results = (0..20).map(|i| if i % 4 == 0 {-0.1} else {0.2}).collect();
+        let codons = vec![Codon::Terminal(Terminal::new(0))];
+        let oe = ObjectiveEvaluator::new(FitnessFunction::new(args, results, 1.0), Box::new(Equity));
+        let mut p = Program::new(&codons, 1, 1);
+        assert_eq!(oe.evaluate(1, &mut p), oe.market.evaluate(1, &mut p));
+        let oe = ObjectiveEvaluator::new(oe.market, Box::new(HitRate));
+        let returns = oe.market.returns(1, true, &mut p);
+        assert_eq!(oe.evaluate(1, &mut p), HitRate.score(&returns, 0.0));
+    }
+}
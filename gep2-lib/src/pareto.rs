@@ -0,0 +1,218 @@
+use std::cmp::Ordering;
+use crate::fitness_evaluator::FitnessFunction;
+use crate::objectives::{self, Objective};
+use crate::program::Program;
+
+// evaluator of several objectives at once, all objectives are maximized
+// (objectives to be minimized are negated)
+pub trait MultiObjectiveEvaluator: Sync {
+    fn objectives(&self, max_delay: usize, program: &mut Program) -> Vec<f32>;
+}
+
+// a dominates b: a is not worse in any objective and better in at least one
+pub fn dominates(a: &[f32], b: &[f32]) -> bool {
+    a.iter().zip(b).all(|(x, y)| x >= y) && a.iter().zip(b).any(|(x, y)| x > y)
+}
+
+// fronts of indexes into objs, the first front is non-dominated
+pub fn non_dominated_sort(objs: &[Vec<f32>]) -> Vec<Vec<usize>> {
+    let n = objs.len();
+    let mut dominated: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut domination_cnt = vec![0usize; n];
+    for i in 0..n {
+        for j in 0..n {
+            if dominates(&objs[i], &objs[j]) {
+                dominated[i].push(j);
+            } else if dominates(&objs[j], &objs[i]) {
+                domination_cnt[i] += 1;
+            }
+        }
+    }
+    let mut fronts = Vec::new();
+    let mut front: Vec<usize> = (0..n).filter(|i| domination_cnt[*i] == 0).collect();
+    while !front.is_empty() {
+        let mut next = Vec::new();
+        for &i in &front {
+            for &j in &dominated[i] {
+                domination_cnt[j] -= 1;
+                if domination_cnt[j] == 0 { next.push(j) }
+            }
+        }
+        fronts.push(front);
+        front = next;
+    }
+    fronts
+}
+
+// crowding distance of every member of a front (in the order of the front);
+// members at the boundary of any objective get infinity
+pub fn crowding_distance(objs: &[Vec<f32>], front: &[usize]) -> Vec<f32> {
+    let mut distance = vec![0.0f32; front.len()];
+    if front.is_empty() { return distance }
+    // values of one objective for every member
+    let columns = (0..objs[front[0]].len()).map(|m| front.iter().map(|i| objs[*i][m]).collect::<Vec<f32>>());
+    for value in columns {
+        let mut order: Vec<usize> = (0..front.len()).collect();
+        order.sort_by(|a, b| value[*a].partial_cmp(&value[*b]).unwrap_or(Ordering::Equal));
+        let lo = value[order[0]];
+        let hi = value[order[order.len() - 1]];
+        distance[order[0]] = f32::INFINITY;
+        distance[order[order.len() - 1]] = f32::INFINITY;
+        if hi - lo <= 0.0 { continue }
+        for k in 1..order.len().saturating_sub(1) {
+            distance[order[k]] += (value[order[k + 1]] - value[order[k - 1]]) / (hi - lo);
+        }
+    }
+    distance
+}
+
+// rank (index of the front) and crowding distance of every member
+pub fn rank_and_crowding(objs: &[Vec<f32>]) -> (Vec<usize>, Vec<f32>) {
+    let mut rank = vec![0; objs.len()];
+    let mut crowding = vec![0.0; objs.len()];
+    for (r, front) in non_dominated_sort(objs).iter().enumerate() {
+        for (i, d) in front.iter().zip(crowding_distance(objs, front)) {
+            rank[*i] = r;
+            crowding[*i] = d;
+        }
+    }
+    (rank, crowding)
+}
+
+// crowded comparison: lower rank first, then larger crowding distance
+pub fn crowded_cmp(rank: &[usize], crowding: &[f32], a: usize, b: usize) -> Ordering {
+    rank[a].cmp(&rank[b]).then(crowding[b].partial_cmp(&crowding[a]).unwrap_or(Ordering::Equal))
+}
+
+// indexes of the n best members by crowded comparison (NSGA-II environmental selection)
+pub fn select(objs: &[Vec<f32>], n: usize) -> Vec<usize> {
+    let (rank, crowding) = rank_and_crowding(objs);
+    let mut order: Vec<usize> = (0..objs.len()).collect();
+    order.sort_by(|a, b| crowded_cmp(&rank, &crowding, *a, *b));
+    order.truncate(n);
+    order
+}
+
+// objective of a trading model
+pub enum Criterion {
+    // net equity divided by the perfect equity
+    Return,
+    // maximum drawdown of the equity curve, negated
+    Drawdown,
+    // sum of absolute position changes, negated
+    Turnover,
+    // number of expressed codons, negated
+    Size,
+    // any single objective (Sharpe, Calmar, ...)
+    Objective(Box<dyn Objective>),
+}
+
+impl Criterion {
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "Return" => Some(Criterion::Return),
+            "Drawdown" => Some(Criterion::Drawdown),
+            "Turnover" => Some(Criterion::Turnover),
+            "Size" => Some(Criterion::Size),
+            _ => objectives::from_symbol(symbol).map(Criterion::Objective),
+        }
+    }
+}
+
+// trading model scored on the train data by several criteria
+pub struct TradingCriteria {
+    pub market: FitnessFunction,
+    pub criteria: Vec<Criterion>,
+}
+
+impl TradingCriteria {
+    pub fn new(market: FitnessFunction, criteria: Vec<Criterion>) -> Self {
+        TradingCriteria { market, criteria }
+    }
+
+    // criteria given by symbol; None if any symbol is unknown
+    pub fn from_symbols(market: FitnessFunction, symbols: &[String]) -> Option<Self> {
+        let mut criteria = Vec::with_capacity(symbols.len());
+        for s in symbols {
+            criteria.push(Criterion::from_symbol(s)?);
+        }
+        Some(TradingCriteria { market, criteria })
+    }
+}
+
+impl MultiObjectiveEvaluator for TradingCriteria {
+    fn objectives(&self, max_delay: usize, program: &mut Program) -> Vec<f32> {
+        let trades = self.market.trades(max_delay, true, program);
+        let returns: Vec<f32> = trades.iter().map(|t| t.1).collect();
+        let perfect = self.market.perfect_equity(true);
+        self.criteria.iter().map(|c| match c {
+            Criterion::Return => if perfect > 0.0 {returns.iter().sum::<f32>() / perfect} else {0.0},
+            Criterion::Drawdown => {
+                let (mut equity, mut peak, mut max_dd) = (0.0f32, 0.0f32, 0.0f32);
+                for r in &returns {
+                    equity += r;
+                    peak = peak.max(equity);
+                    max_dd = max_dd.max(peak - equity);
+                }
+                -max_dd
+            }
+            Criterion::Turnover => {
+                let mut prev = 0.0;
+                -trades.iter().map(|(s, _)| { let t = (s - prev).abs(); prev = *s; t }).sum::<f32>()
+            }
+            Criterion::Size => -(program.size() as f32),
+            Criterion::Objective(o) => o.score(&returns, perfect),
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<Vec<f32>> {
+        vec![
+            vec![1.0, 5.0], // 0: front 0
+            vec![2.0, 4.0], // 1: front 0
+            vec![1.5, 3.0], // 2: front 1 (dominated by 1)
+            vec![4.0, 1.0], // 3: front 0
+            vec![1.0, 1.0], // 4: front 2
+            vec![1.0, 5.0], // 5: equal to 0, front 0
+        ]
+    }
+
+    #[test]
+    fn non_dominated_sort_test() {
+        assert!(dominates(&[1.0, 2.0], &[1.0, 1.0]));
+        assert!(!dominates(&[1.0, 1.0], &[1.0, 1.0]));
+        assert!(!dominates(&[2.0, 0.0], &[1.0, 1.0]));
+        let fronts = non_dominated_sort(&sample());
+        assert_eq!(fronts, vec![vec![0, 1, 3, 5], vec![2], vec![4]]);
+    }
+
+    #[test]
+    fn crowding_distance_test() {
+        let objs = vec![vec![0.0, 4.0], vec![1.0, 3.0], vec![3.0, 1.0], vec![4.0, 0.0]];
+        let d = crowding_distance(&objs, &[0, 1, 2, 3]);
+        assert_eq!(d[0], f32::INFINITY);
+        assert_eq!(d[3], f32::INFINITY);
+        // (3 - 0) / 4 for both objectives
+        assert_eq!(d[1], 1.5);
+        assert_eq!(d[2], 1.5);
+        assert_eq!(crowding_distance(&objs, &[2]), vec![f32::INFINITY]);
+    }
+
+    #[test]
+    fn select_test() {
+        let objs = sample();
+        let s = select(&objs, 5);
+        assert_eq!(s.len(), 5);
+        // the whole first front and then the second one
+        for i in [0, 1, 3, 5, 2] {
+            assert!(s.contains(&i));
+        }
+        let s = select(&objs, 2);
+        // boundary members of the first front are kept
+        assert!(s.iter().all(|i| [0, 3, 5].contains(i)));
+    }
+}
@@ -1,15 +1,155 @@
 extern crate pbr;
+use std::sync::Arc;
 use pbr::ProgressBar;
 
-use rand::prelude::ThreadRng;
-use rand::Rng;
-use crate::chromosome::Chromosome;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use crate::chromosome::{Chromosome, CrossoverMode};
+use crate::function_set::FunctionSet;
+use crate::linking_function::{LinkingFunction, Majority};
 use crate::fitness_evaluator::FitnessEvaluator;
+use crate::pareto::{self, MultiObjectiveEvaluator};
+use crate::schedule::{Progress, Schedule};
+use crate::selection::{Roulette, Selection};
+
+// probabilities of the optional GEP operators, all zero by default
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct OperatorRates {
+    pub is_transposition: f32,
+    pub gene_transposition: f32,
+    pub inversion: f32,
+    pub one_point_crossover: f32,
+    pub gene_recombination: f32,
+    // random numerical constants (GEP-RNC), with FunctionSet::set_constants only
+    pub dc_transposition: f32,
+    pub constant_mutation: f32,
+}
+
+// operator rates used to produce a generation
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Rates {
+    pub mutation: f32,
+    pub transposition: f32,
+    pub crossover: f32,
+    pub operators: OperatorRates,
+}
+
+// max. and avg. fitness, k-string and index of the best chromosome and rates of a generation
+pub type GenerationStat = (f32, f32, String, usize, Rates);
+
+// names of operators whose rates can follow a schedule
+pub const OPERATORS: [&str; 10] = ["mutation", "transposition", "crossover", "is_transposition",
+    "gene_transposition", "inversion", "one_point_crossover", "gene_recombination", "dc_transposition",
+    "constant_mutation"];
+
+impl Rates {
+    pub fn get_mut(&mut self, operator: &str) -> Option<&mut f32> {
+        match operator {
+            "mutation" => Some(&mut self.mutation),
+            "transposition" => Some(&mut self.transposition),
+            "crossover" => Some(&mut self.crossover),
+            "is_transposition" => Some(&mut self.operators.is_transposition),
+            "gene_transposition" => Some(&mut self.operators.gene_transposition),
+            "inversion" => Some(&mut self.operators.inversion),
+            "one_point_crossover" => Some(&mut self.operators.one_point_crossover),
+            "gene_recombination" => Some(&mut self.operators.gene_recombination),
+            "dc_transposition" => Some(&mut self.operators.dc_transposition),
+            "constant_mutation" => Some(&mut self.operators.constant_mutation),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, operator: &str) -> Option<f32> {
+        let mut rates = *self;
+        rates.get_mut(operator).map(|r| *r)
+    }
+}
+
+// state of a single-objective search after a generation, enough to continue it exactly
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    // finished generations and generations of the whole search
+    pub generation: usize,
+    pub generations: usize,
+    best: f32,
+    success: f32,
+    raw_fitness: Vec<f32>,
+    // TOML needs tables after values
+    rng: RngState,
+    initial: Rates,
+    rates: Rates,
+    stat: Vec<StatData>,
+    chromosomes: Vec<Chromosome>,
+}
+
+// position of the generator in its stream; word_pos is stored as u64, which TOML can hold
+#[derive(Serialize, Deserialize)]
+struct RngState {
+    seed: [u8; 32],
+    stream: u64,
+    word_pos: u64,
+}
+
+impl RngState {
+    fn new(rng: &ChaCha12Rng) -> Self {
+        RngState { seed: rng.get_seed(), stream: rng.get_stream(),
+            word_pos: u64::try_from(rng.get_word_pos()).expect("generator position out of range") }
+    }
+
+    fn rng(&self) -> ChaCha12Rng {
+        let mut rng = ChaCha12Rng::from_seed(self.seed);
+        rng.set_stream(self.stream);
+        rng.set_word_pos(u128::from(self.word_pos));
+        rng
+    }
+}
+
+// serialized GenerationStat
+#[derive(Serialize, Deserialize)]
+struct StatData {
+    max: f32,
+    avg: f32,
+    k_string: String,
+    best: usize,
+    rates: Rates,
+}
+
+// shape, building blocks and random generator of a new population
+pub struct Settings {
+    pub size: usize,
+    pub gene_nbr: usize,
+    pub head_length: usize,
+    pub num_args: usize,
+    pub max_delay: usize,
+    pub transposition_probability: f32,
+    pub mutation_probability: f32,
+    pub function_set: FunctionSet,
+    pub linking_function: Arc<dyn LinkingFunction>,
+    // a fixed seed makes the whole search reproducible
+    pub seed: Option<u64>,
+}
+
+impl Default for Settings {
+    // built-in functions, majority vote and a seed from entropy
+    fn default() -> Self {
+        Settings { size: 50, gene_nbr: 3, head_length: 6, num_args: 1, max_delay: 0,
+            transposition_probability: 0.3, mutation_probability: 0.2, function_set: FunctionSet::default(),
+            linking_function: Arc::new(Majority), seed: None }
+    }
+}
 
 pub struct Population {
-    pub rng: ThreadRng,
+    pub rng: ChaCha12Rng,
     pub chromosomes: Vec<Chromosome>,
+    pub function_set: FunctionSet,
+    pub linking_function: Arc<dyn LinkingFunction>,
+    // parents of the next generation, roulette wheel by default
+    pub selection: Box<dyn Selection>,
     pub size:usize,
+    elite_count: usize,
     pub gene_nbr:usize,
     pub hl:usize,
     pub num_args:usize,
@@ -17,21 +157,45 @@ pub struct Population {
     denominator: f32,
     transposition_probability: f32, // def. val. 0.3
     mutation_probability: f32, // def. val. 0.2
+    rates: OperatorRates,
+    crossover_mode: CrossoverMode,
+    crossover_probability: f32,
+    // number and head length of homeotic genes, none by default
+    homeotic: (usize, usize),
+    // rate schedules by operator name, rates are fixed without one
+    schedules: Vec<(String, Schedule)>,
+    // fitness before normalization, of the selected parent of every offspring and
+    // fraction of offspring better than their parents in the last generation
+    raw_fitness: Vec<f32>,
+    parent_fitness: Vec<f32>,
+    success: f32,
+    // search stops once max. fitness exceeds it, None - runs all generations
+    target_fitness: Option<f32>,
+    // generations between checkpoints (0 - none) and where they go
+    checkpoint_every: usize,
+    save_checkpoint: Box<dyn FnMut(&Checkpoint) + Send>,
+    // None - sequential evaluation
+    #[cfg(feature = "parallel")]
+    pool: Option<rayon::ThreadPool>,
 }
 
 impl Population{
-    pub fn new(mut size:usize,gn:usize, mut hl:usize, mut num_args:usize, max_delay: usize,
-               mut transposition_probability : f32,
-               mut mutation_probability : f32) -> Self{
+    pub fn new(settings: Settings) -> Self{
+        let Settings { mut size, gene_nbr: gn, head_length: mut hl, mut num_args, max_delay,
+            mut transposition_probability, mut mutation_probability, mut function_set, linking_function, seed } = settings;
         if num_args == 0 {num_args = 1}
         let mut d = 0.0;
-        let mut rg = rand::thread_rng();
+        let mut rg = match seed {
+            Some(s) => ChaCha12Rng::seed_from_u64(s),
+            None => ChaCha12Rng::from_entropy(),
+        };
         if size < 2 {size = 2}
         if hl < 1 {hl = 1}
+        if function_set.is_empty() {function_set = FunctionSet::default()}
         let mut chr: Vec<Chromosome> = Vec::with_capacity(size);
         for _i in 0..size{
             d += 1.0;
-            chr.push(Chromosome::new(&mut rg, gn, hl, num_args, max_delay))
+            chr.push(Chromosome::new(&mut rg, &function_set, &linking_function, gn, hl, num_args, max_delay))
         }
         if transposition_probability > 1.0 {
             transposition_probability = 1.0;
@@ -45,53 +209,176 @@ impl Population{
         Population{
             rng: rg,
             chromosomes: chr,
+            function_set,
+            linking_function,
+            selection: Box::new(Roulette),
             size,
+            elite_count: 1,
             gene_nbr:gn,
             hl,
             num_args,
             max_delay,
             denominator:d,
             transposition_probability,
-            mutation_probability
+            mutation_probability,
+            rates: OperatorRates::default(),
+            crossover_mode: CrossoverMode::Free,
+            crossover_probability: 1.0,
+            homeotic: (0, 0),
+            schedules: Vec::new(),
+            raw_fitness: Vec::new(),
+            parent_fitness: Vec::new(),
+            success: 0.0,
+            target_fitness: Some(0.95),
+            checkpoint_every: 0,
+            save_checkpoint: Box::new(|_| {}),
+            #[cfg(feature = "parallel")]
+            pool: None,
         }
     }
 
-    pub fn search<'a>(&'a mut self, fe: &'a impl FitnessEvaluator, g:usize) -> Vec<(f32, f32, String, usize)>{
-        let mut stat: Vec<(f32,f32, String, usize)> = Vec::with_capacity(g);
-        let mut r : Option<(f32,f32, String, usize)>;
-        // initialization
-        loop {
-            r = self.evaluate(fe);
-            if r != None {break}
-            // total fitness is about zero - reinitialize
-            for i in 0..self.size{
-                self.chromosomes[i] = Chromosome::new(&mut self.rng, self.gene_nbr,  self.hl, self.num_args, self.max_delay);
-            }
+    // 0 - use all available cores, 1 - sequential evaluation;
+    // without the "parallel" feature evaluation is always sequential
+    pub fn set_threads(&mut self, threads: usize) {
+        #[cfg(feature = "parallel")]
+        {
+            self.pool = if threads == 1 { None } else {
+                Some(rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap())
+            };
         }
-        stat.push(r.unwrap());
+        #[cfg(not(feature = "parallel"))]
+        let _ = threads;
+    }
 
-        let mut pb = ProgressBar::new(g as u64);
+    // statistics of every generation: max. and avg. fitness, k-string and index of the best
+    // chromosome and the rates used to produce the generation; scheduled rates are adapted after
+    // every generation and restored when the search is over
+    pub fn search<'a>(&'a mut self, fe: &'a (impl FitnessEvaluator + ?Sized), g:usize) -> Vec<GenerationStat>{
+        let mut stat: Vec<GenerationStat> = Vec::with_capacity(g);
+        // initialization
+        let (mf, avg, k, ii) = self.evaluate_or_reinitialize(fe);
+        let initial = self.rates();
+        stat.push((mf, avg, k, ii, initial));
+        self.run(fe, stat, 0, g, initial, mf)
+    }
+
+    // continues the search of a checkpoint for g more generations; the population must be set up
+    // like the one that made it to get the results of an uninterrupted search; statistics include
+    // the generations before the checkpoint; None if the checkpoint holds another number of
+    // chromosomes
+    pub fn resume<'a>(&'a mut self, fe: &'a (impl FitnessEvaluator + ?Sized), checkpoint: Checkpoint, g: usize) -> Option<Vec<GenerationStat>> {
+        if checkpoint.chromosomes.len() != self.size { return None }
+        self.chromosomes = checkpoint.chromosomes;
+        self.rng = checkpoint.rng.rng();
+        self.raw_fitness = checkpoint.raw_fitness;
+        self.success = checkpoint.success;
+        self.set_rates(checkpoint.rates);
+        let stat = checkpoint.stat.into_iter().map(|s| (s.max, s.avg, s.k_string, s.best, s.rates)).collect();
+        Some(self.run(fe, stat, checkpoint.generation, checkpoint.generation + g, checkpoint.initial, checkpoint.best))
+    }
+
+    // generations start..g of a search, best is the highest max. fitness so far
+    fn run(&mut self, fe: &(impl FitnessEvaluator + ?Sized), mut stat: Vec<GenerationStat>,
+           start: usize, g: usize, initial: Rates, mut best: f32) -> Vec<GenerationStat> {
+        let mut pb = ProgressBar::new((g - start) as u64);
         pb.format("╢▌▌░╟");
         // gp-search
-        for i in 0..g {
+        for i in start..g {
             pb.inc();
+            let rates = self.rates();
             self.update();
-            stat.push(self.evaluate(fe).unwrap());
-            if stat[i+1].0 > 0.95 {break}
+            let (mf, avg, k, ii) = self.evaluate_or_reinitialize(fe);
+            stat.push((mf, avg, k, ii, rates));
+            if self.target_fitness.is_some_and(|t| mf > t) {break}
+            let progress = Progress { generation: i, generations: g, improved: mf > best, success: self.success };
+            best = best.max(mf);
+            self.adapt(&initial, &progress);
+            if self.checkpoint_every > 0 && (i + 1) % self.checkpoint_every == 0 {
+                self.checkpoint(i + 1, g, initial, best, &stat);
+            }
             //progress bar
         }
         pb.finish_print("done");
+        self.set_rates(initial);
         stat
     }
 
-    // because of elitism we can return None within phase of initialization only
-    pub fn evaluate<'a>(&'a self, fe: &'a impl FitnessEvaluator) -> Option<(f32, f32, String, usize)> {
+    // a checkpoint is passed to save after every `every` generations of search (not of
+    // search_pareto); 0 - none
+    pub fn set_checkpoints(&mut self, every: usize, save: Box<dyn FnMut(&Checkpoint) + Send>) {
+        self.checkpoint_every = every;
+        self.save_checkpoint = save;
+    }
+
+    fn checkpoint(&mut self, generation: usize, generations: usize, initial: Rates, best: f32, stat: &[GenerationStat]) {
+        let checkpoint = Checkpoint {
+            generation,
+            generations,
+            best,
+            success: self.success,
+            raw_fitness: self.raw_fitness.clone(),
+            rng: RngState::new(&self.rng),
+            initial,
+            rates: self.rates(),
+            stat: stat.iter().map(|s| StatData { max: s.0, avg: s.1, k_string: s.2.clone(), best: s.3, rates: s.4 }).collect(),
+            chromosomes: self.chromosomes.iter().map(|c| {
+                let mut copy = c.copy_to_new_generation();
+                copy.fitness = c.fitness;
+                copy
+            }).collect(),
+        };
+        (self.save_checkpoint)(&checkpoint);
+    }
+
+    // rate of an operator (see OPERATORS) follows the schedule during the search, starting
+    // from the configured rate; false if the operator is unknown
+    pub fn set_schedule(&mut self, operator: &str, schedule: Schedule) -> bool {
+        if Rates::default().get_mut(operator).is_none() { return false }
+        self.schedules.retain(|(o, _)| o != operator);
+        self.schedules.push((operator.to_string(), schedule));
+        true
+    }
+
+    pub fn rates(&self) -> Rates {
+        Rates {
+            mutation: self.mutation_probability,
+            transposition: self.transposition_probability,
+            crossover: self.crossover_probability,
+            operators: self.rates,
+        }
+    }
+
+    fn set_rates(&mut self, rates: Rates) {
+        self.mutation_probability = rates.mutation;
+        self.transposition_probability = rates.transposition;
+        self.crossover_probability = rates.crossover;
+        self.rates = rates.operators;
+    }
+
+    fn adapt(&mut self, initial: &Rates, progress: &Progress) {
+        let mut rates = self.rates();
+        for (operator, schedule) in &self.schedules {
+            let start = initial.get(operator).unwrap();
+            let rate = rates.get_mut(operator).unwrap();
+            *rate = schedule.next(start, *rate, progress);
+        }
+        self.set_rates(rates);
+    }
+
+    // None if total fitness is about zero, with or without elitism
+    pub fn evaluate(&mut self, fe: &(impl FitnessEvaluator + ?Sized)) -> Option<(f32, f32, String, usize)> {
+        self.pass_all(fe);
+        self.raw_fitness = self.chromosomes.iter().map(|c| c.fitness).collect();
+        // offspring follow the elite in the order of their parents
+        let offspring = &self.raw_fitness[self.size - self.parent_fitness.len()..];
+        let better = offspring.iter().zip(&self.parent_fitness).filter(|(o, p)| o > p).count();
+        self.success = if self.parent_fitness.is_empty() {0.0} else {better as f32 / self.parent_fitness.len() as f32};
         let mut total = 0.0;
         let mut mf=-1.0;
         let mut ii = 0;
         let mut i = 0;
         for chr in  & self.chromosomes{
-            let f = chr.pass(self.max_delay,fe);
+            let f = chr.fitness;
             if f > mf{
                 mf = f;
                 ii = i;
@@ -102,71 +389,459 @@ impl Population{
         if total < 0.001 {return None;}
         let avg = total/self.denominator;
         for i in 0..self.size{
-            let c = &self.chromosomes[i];
-            let fi = c.fitness.get();
-            c.fitness.set(fi/total);
+            self.chromosomes[i].fitness /= total;
         }
         Some((mf,avg,self.chromosomes[ii].k_string(), ii))
     }
 
-    fn update(&mut self) {
-        let (wheel, wheel_ids, id_of_best) = self.build_roulette_wheel();
-        let mut next_generation: Vec<Chromosome> = Vec::with_capacity(self.size);
-        // copy chromosomes with positive fitness (optionally mutated) to new generation
-        for _i in 0..self.size{
-            let test = self.rng.gen_range(0.0..1.0);
-            for ii in 0..wheel.len() {
-                if test < wheel[ii] {
-                    if self.mutation_probability > 0.0 {
-                        next_generation.push(self.chromosomes[wheel_ids[ii]].mutation(&mut self.rng, self.num_args, self.max_delay, self.mutation_probability));
-                    }
-                    else{
-                        next_generation.push(self.chromosomes[wheel_ids[ii]].copy_to_new_generation());
-                    }
-                    break;
+    // a generation of about zero total fitness is replaced by random chromosomes until
+    // one can be normalized
+    fn evaluate_or_reinitialize(&mut self, fe: &(impl FitnessEvaluator + ?Sized)) -> (f32, f32, String, usize) {
+        loop {
+            if let Some(r) = self.evaluate(fe) { return r }
+            for i in 0..self.size{
+                self.chromosomes[i] = Chromosome::new(&mut self.rng, &self.function_set, &self.linking_function, self.gene_nbr,  self.hl, self.num_args, self.max_delay);
+                if self.homeotic.0 > 0 {
+                    self.chromosomes[i].set_homeotic(&mut self.rng, &self.function_set, self.homeotic.0, self.homeotic.1);
                 }
             }
+            // new chromosomes have no parents
+            self.parent_fitness.clear();
         }
-        // apply root_transposition to chromosomes in a new generation as per a given probability
-        for i in 0..self.size{
-            if self.transposition_probability <= 0.0 {
-                continue;
+    }
+
+    // chromosomes are scored independently, so the order of evaluation does not affect results
+    fn pass_all(&mut self, fe: &(impl FitnessEvaluator + ?Sized)) {
+        let max_delay = self.max_delay;
+        #[cfg(feature = "parallel")]
+        if let Some(ref pool) = self.pool {
+            let chromosomes = &mut self.chromosomes;
+            pool.install(|| chromosomes.par_iter_mut().for_each(|chr| { chr.pass(max_delay, fe); }));
+            return;
+        }
+        for chr in self.chromosomes.iter_mut() {
+            chr.pass(max_delay, fe);
+        }
+    }
+
+    // multi-objective search (NSGA-II): offspring are bred from parents chosen by crowded
+    // tournaments, then parents and offspring together are cut back to the population size by
+    // non-dominated sorting and crowding distance; returns k-string, objectives and index
+    // of every member of the final Pareto front
+    pub fn search_pareto(&mut self, fe: &(impl MultiObjectiveEvaluator + ?Sized), g: usize) -> Vec<(String, Vec<f32>, usize)> {
+        let mut objs = self.objectives_all(&self.chromosomes, fe);
+        let mut pb = ProgressBar::new(g as u64);
+        pb.format("╢▌▌░╟");
+        for _ in 0..g {
+            pb.inc();
+            let (rank, crowding) = pareto::rank_and_crowding(&objs);
+            let offspring = self.breed(&rank, &crowding);
+            objs.extend(self.objectives_all(&offspring, fe));
+            self.chromosomes.extend(offspring);
+            let survivors = pareto::select(&objs, self.size);
+            self.chromosomes = survivors.iter().map(|i| self.chromosomes[*i].copy_to_new_generation()).collect();
+            objs = survivors.iter().map(|i| objs[*i].clone()).collect();
+        }
+        pb.finish_print("done");
+        pareto::non_dominated_sort(&objs)[0].iter()
+            .map(|i| (self.chromosomes[*i].k_string(), objs[*i].clone(), *i))
+            .collect()
+    }
+
+    fn objectives_all(&self, chromosomes: &[Chromosome], fe: &(impl MultiObjectiveEvaluator + ?Sized)) -> Vec<Vec<f32>> {
+        let max_delay = self.max_delay;
+        #[cfg(feature = "parallel")]
+        if let Some(ref pool) = self.pool {
+            return pool.install(|| chromosomes.par_iter().map(|chr| chr.objectives(max_delay, fe)).collect());
+        }
+        chromosomes.iter().map(|chr| chr.objectives(max_delay, fe)).collect()
+    }
+
+    // offspring of binary crowded tournaments, varied like in update
+    fn breed(&mut self, rank: &[usize], crowding: &[f32]) -> Vec<Chromosome> {
+        let mut offspring: Vec<Chromosome> = Vec::with_capacity(self.size);
+        for _i in 0..self.size {
+            let a = self.rng.gen::<usize>() % self.size;
+            let b = self.rng.gen::<usize>() % self.size;
+            let w = if pareto::crowded_cmp(rank, crowding, b, a) == std::cmp::Ordering::Less {b} else {a};
+            if self.mutation_probability > 0.0 {
+                offspring.push(self.chromosomes[w].mutation(&mut self.rng, &self.function_set, self.num_args, self.max_delay, self.mutation_probability));
+            }
+            else{
+                offspring.push(self.chromosomes[w].copy_to_new_generation());
             }
-            next_generation[i].root_transposition(&mut self.rng, self.transposition_probability);
         }
-        // apply two points crossover to chromosomes in a new generation
-        for i in (0..self.size).step_by(2){
-            Chromosome::two_points_crossover(&mut next_generation, i, i+1, &mut self.rng);
+        self.vary(&mut offspring);
+        offspring
+    }
+
+    // probabilities of IS/gene transposition, inversion, one-point crossover, gene recombination,
+    // Dc transposition and constant mutation; values are clamped to [0,1]
+    pub fn set_operator_rates(&mut self, rates: OperatorRates) {
+        let c = |p: f32| p.clamp(0.0, 1.0);
+        self.rates = OperatorRates {
+            is_transposition: c(rates.is_transposition),
+            gene_transposition: c(rates.gene_transposition),
+            inversion: c(rates.inversion),
+            one_point_crossover: c(rates.one_point_crossover),
+            gene_recombination: c(rates.gene_recombination),
+            dc_transposition: c(rates.dc_transposition),
+            constant_mutation: c(rates.constant_mutation),
+        };
+    }
+
+    // two-point crossover of every pair with the given probability (clamped to [0,1]);
+    // by default free cut points and probability 1
+    pub fn set_crossover(&mut self, mode: CrossoverMode, probability: f32) {
+        self.crossover_mode = mode;
+        self.crossover_probability = probability.clamp(0.0, 1.0);
+    }
+
+    // transpositions, inversion, constant mutation and crossovers of pairs of chromosomes (an odd last one is not crossed)
+    fn vary(&mut self, chrs: &mut [Chromosome]) {
+        let rates = self.rates;
+        for chr in chrs.iter_mut() {
+            if self.transposition_probability > 0.0 {
+                chr.root_transposition(&mut self.rng, self.transposition_probability);
+            }
+            chr.is_transposition(&mut self.rng, rates.is_transposition);
+            chr.gene_transposition(&mut self.rng, rates.gene_transposition);
+            chr.inversion(&mut self.rng, rates.inversion);
+            chr.dc_transposition(&mut self.rng, rates.dc_transposition);
+            chr.constant_mutation(&mut self.rng, &self.function_set, rates.constant_mutation);
+        }
+        for i in (0..chrs.len().saturating_sub(1)).step_by(2){
+            if rates.one_point_crossover > 0.0 && self.rng.gen_range(0.0..1.0) < rates.one_point_crossover {
+                Chromosome::one_point_crossover(chrs, i, i+1, &mut self.rng);
+            }
+            let p = self.crossover_probability;
+            if p >= 1.0 || (p > 0.0 && self.rng.gen_range(0.0..1.0) < p) {
+                Chromosome::two_points_crossover_with(chrs, i, i+1, self.crossover_mode, &mut self.rng);
+            }
+            if rates.gene_recombination > 0.0 && self.rng.gen_range(0.0..1.0) < rates.gene_recombination {
+                Chromosome::gene_recombination(chrs, i, i+1, &mut self.rng);
+            }
         };
-        // some elitism :)
-        next_generation[0] = self.chromosomes[id_of_best].copy_to_new_generation();
+    }
+
+    // every chromosome gets `nbr` random homeotic genes (GEP-ADF) with the given head length,
+    // its genes become ADFs combined by the homeotic genes; 0 - none
+    pub fn set_homeotic(&mut self, nbr: usize, head_length: usize) {
+        self.homeotic = (nbr, head_length.max(1));
+        for chr in self.chromosomes.iter_mut() {
+            chr.set_homeotic(&mut self.rng, &self.function_set, nbr, head_length);
+        }
+    }
+
+    // search stops early once max. fitness exceeds the target (default 0.95); None - never
+    pub fn set_target_fitness(&mut self, target: Option<f32>) {
+        self.target_fitness = target;
+    }
+
+    pub fn set_selection(&mut self, selection: Box<dyn Selection>) {
+        self.selection = selection;
+    }
+
+    // number of best chromosomes copied unchanged to the next generation (default 1);
+    // at most the population size
+    pub fn set_elite_count(&mut self, elite_count: usize) {
+        self.elite_count = elite_count.min(self.size);
+    }
+
+    fn update(&mut self) {
+        let fitness: Vec<f32> = self.chromosomes.iter().map(|c| c.fitness).collect();
+        let elite = self.elite_count;
+        let mut next_generation: Vec<Chromosome> = Vec::with_capacity(self.size);
+        // elitism: the best chromosomes are copied unchanged and take no part in variation
+        for i in self.ranked().into_iter().take(elite) {
+            next_generation.push(self.chromosomes[i].copy_to_new_generation());
+        }
+        let parents = self.selection.select(&fitness, self.size - elite, &mut self.rng);
+        self.parent_fitness = parents.iter().map(|i| self.raw_fitness.get(*i).cloned().unwrap_or(0.0)).collect();
+        // copy selected chromosomes (optionally mutated) to new generation
+        for i in parents {
+            if self.mutation_probability > 0.0 {
+                next_generation.push(self.chromosomes[i].mutation(&mut self.rng, &self.function_set, self.num_args, self.max_delay, self.mutation_probability));
+            }
+            else{
+                next_generation.push(self.chromosomes[i].copy_to_new_generation());
+            }
+        }
+        self.vary(&mut next_generation[elite..]);
         // update current population
         self.chromosomes = next_generation;
     }
 
-    fn build_roulette_wheel(&mut self) -> (Vec<f32>, Vec<usize>, usize) {
-        let mut wheel: Vec<f32> = Vec::with_capacity(self.size);
-        let mut wheel_idx: Vec<usize> = Vec::with_capacity(self.size);
-        let mut prev = 0.0;
-        let mut max_ind = 0;
-        let mut max_f = -1.0;
+    // indexes from the highest fitness to the lowest (in order of index on ties)
+    fn ranked(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.size).collect();
+        order.sort_by(|a, b| self.chromosomes[*b].fitness.partial_cmp(&self.chromosomes[*a].fitness).unwrap_or(std::cmp::Ordering::Equal));
+        order
+    }
+
+    pub fn show_pass_results(&self){
         for i in 0..self.size {
-            let f = self.chromosomes[i].fitness.get();
-            if f < 0.0001 { continue }
-            if f > max_f {
-                max_f = f;
-                max_ind = i;
+            println!("chr #{} : {}",i,self.chromosomes[i].fitness)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fitness_evaluator::FitnessFunction;
+    use crate::program::Program;
+
+    fn sample_evaluator() -> FitnessFunction {
+        let mut args = Vec::new();
+        let mut results = Vec::new();
+        for i in 0..200 {
+            let x = (i as f32 * 0.37).sin();
+            let y = (i as f32 * 0.11).cos();
+            args.push(vec![x, y, x * y]);
+            results.push(((i + 1) as f32 * 0.37).sin() - x);
+        }
+        FitnessFunction::new(args, results, 0.7)
+    }
+
+    fn settings(size: usize, gene_nbr: usize, mutation_probability: f32, seed: Option<u64>) -> Settings {
+        Settings { size, gene_nbr, head_length: 4, num_args: 3, max_delay: 2, mutation_probability, seed,
+            ..Settings::default() }
+    }
+
+    #[test]
+    fn same_seed_same_search_test() {
+        let fe = sample_evaluator();
+        let mut p1 = Population::new(settings(20, 2, 0.2, Some(42)));
+        let mut p2 = Population::new(settings(20, 2, 0.2, Some(42)));
+        let s1 = p1.search(&fe, 10);
+        let s2 = p2.search(&fe, 10);
+        assert!(s1 == s2);
+        let best = s1[s1.len() - 1].3;
+        assert_eq!(p1.chromosomes[best].equity(2, false, &fe), p2.chromosomes[best].equity(2, false, &fe));
+    }
+
+    #[test]
+    fn selection_strategies_test() {
+        let fe = sample_evaluator();
+        for s in ["Roulette", "Tour3", "Rank1.5", "Trunc0.3"] {
+            let mut p = Population::new(settings(20, 2, 0.2, Some(11)));
+            p.set_selection(crate::selection::from_symbol(s).unwrap());
+            let stat = p.search(&fe, 5);
+            assert!(stat.iter().all(|s| s.0 > 0.0));
+            assert_eq!(p.chromosomes.len(), 20);
+        }
+    }
+
+    #[test]
+    fn elitism_test() {
+        let fe = sample_evaluator();
+        for elite in [1, 3] {
+            let mut p = Population::new(settings(21, 2, 0.5, Some(5)));
+            p.set_elite_count(elite);
+            p.set_selection(Box::new(crate::selection::Tournament { size: 2 }));
+            let (mut best, ..) = p.evaluate(&fe).unwrap();
+            for _ in 0..15 {
+                let ranked = p.ranked();
+                let elite_codons: Vec<String> = ranked[..elite].iter().map(|i| p.chromosomes[*i].k_string()).collect();
+                p.update();
+                // elite is copied unchanged to the front of the next generation
+                for (chr, codons) in p.chromosomes.iter().zip(&elite_codons) {
+                    assert_eq!(&chr.k_string(), codons);
+                }
+                let (max, ..) = p.evaluate(&fe).unwrap();
+                assert!(max >= best);
+                best = max;
             }
-            prev += f;
-            wheel.push(prev);
-            wheel_idx.push(i);
         }
-        (wheel, wheel_idx, max_ind)
     }
 
-    pub fn show_pass_results(&self){
-        for i in 0..self.size {
-            println!("chr #{} : {}",i,self.chromosomes[i].fitness.get())
+    #[test]
+    fn target_fitness_test() {
+        let fe = sample_evaluator();
+        let mut p = Population::new(settings(20, 2, 0.2, Some(9)));
+        p.set_target_fitness(Some(f32::MIN));
+        assert_eq!(p.search(&fe, 5).len(), 2);
+        p.set_target_fitness(None);
+        assert_eq!(p.search(&fe, 5).len(), 6);
+    }
+
+    // fitness 1 except for the chromosomes of one generation
+    struct Fading {
+        size: usize,
+        zero_generation: usize,
+        calls: std::sync::atomic::AtomicUsize,
+    }
+    impl FitnessEvaluator for Fading {
+        fn evaluate(&self, _max_delay: usize, _program: &mut Program) -> f32 {
+            let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if call / self.size == self.zero_generation {0.0} else {1.0}
+        }
+        fn equity(&self, _max_delay: usize, _train: bool, _program: &mut Program) -> Vec<f32> { Vec::new() }
+        fn outputs(&self, _max_delay: usize, _train: bool, _program: &mut Program) -> Vec<f32> { Vec::new() }
+    }
+
+    #[test]
+    fn zero_fitness_generation_test() {
+        for elite in [0, 1] {
+            let fe = Fading { size: 20, zero_generation: 2, calls: Default::default() };
+            let mut p = Population::new(settings(20, 2, 0.2, Some(6)));
+            p.set_elite_count(elite);
+            p.set_target_fitness(None);
+            // the generation is reinitialized and evaluated again
+            let stat = p.search(&fe, 4);
+            assert_eq!(stat.len(), 5);
+            assert!(stat.iter().all(|s| s.0 == 1.0));
+            assert_eq!(fe.calls.into_inner(), 6 * 20);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn operator_rates_test() {
+        let fe = sample_evaluator();
+        let mut p1 = Population::new(settings(20, 3, 0.2, Some(8)));
+        let mut p2 = Population::new(settings(20, 3, 0.2, Some(8)));
+        // zero rates keep the search as it was
+        p2.set_operator_rates(OperatorRates::default());
+        assert!(p1.search(&fe, 5) == p2.search(&fe, 5));
+        p2.set_operator_rates(OperatorRates { is_transposition: 0.5, gene_transposition: 2.0, inversion: 0.5,
+            one_point_crossover: 0.5, gene_recombination: -1.0, dc_transposition: 0.5, constant_mutation: 0.1 });
+        assert_eq!(p2.rates.gene_transposition, 1.0);
+        assert_eq!(p2.rates.gene_recombination, 0.0);
+        let stat = p2.search(&fe, 5);
+        assert!(stat.iter().all(|s| s.0 > 0.0));
+    }
+
+    #[test]
+    fn constants_search_test() {
+        let fe = sample_evaluator();
+        let mut fs = FunctionSet::default();
+        fs.set_constants(10, -1.0, 1.0);
+        let mut p = Population::new(Settings { function_set: fs, ..settings(20, 2, 0.2, Some(12)) });
+        p.set_operator_rates(OperatorRates { dc_transposition: 0.3, constant_mutation: 0.05, ..OperatorRates::default() });
+        let stat = p.search(&fe, 5);
+        assert!(stat.iter().all(|s| s.0 > 0.0 && s.2.contains(" Dc(")));
+    }
+
+    #[test]
+    fn homeotic_search_test() {
+        let fe = sample_evaluator();
+        for linker in ["Maj", "WSum"] {
+            let lf = crate::linking_function::from_symbol(linker).unwrap();
+            let mut p = Population::new(Settings { linking_function: lf, ..settings(20, 3, 0.2, Some(13)) });
+            p.set_homeotic(2, 3);
+            p.set_operator_rates(OperatorRates { gene_recombination: 0.5, gene_transposition: 0.3, one_point_crossover: 0.3,
+                ..OperatorRates::default() });
+            let stat = p.search(&fe, 5);
+            assert!(stat.iter().all(|s| s.0 > 0.0 && s.2.contains(" ##")));
+            assert!(p.chromosomes.iter().all(|c| c.homeotic.len() == 2 * 7));
+            assert!(p.chromosomes.iter().all(|c| c.weights.len() == if linker == "WSum" {2} else {0}));
+        }
+    }
+
+    #[test]
+    fn schedules_test() {
+        let fe = sample_evaluator();
+        let mut p = Population::new(settings(20, 2, 0.4, Some(3)));
+        assert!(!p.set_schedule("mutate", Schedule::Constant));
+        assert!(p.set_schedule("mutation", Schedule::Exponential(0.5)));
+        assert!(p.set_schedule("inversion", Schedule::Linear(0.2)));
+        assert!(p.set_schedule("crossover", Schedule::OneFifth(2.0)));
+        let stat = p.search(&fe, 4);
+        // rates used for every generation, the first one is the initial population
+        let mutation: Vec<f32> = stat.iter().map(|s| s.4.mutation).collect();
+        assert_eq!(mutation, [0.4, 0.4, 0.2, 0.1, 0.05][..stat.len()]);
+        for (i, s) in stat.iter().enumerate().skip(1) {
+            assert!((s.4.operators.inversion - 0.05 * (i - 1) as f32).abs() < 1e-6);
+            assert!((0.0..=1.0).contains(&s.4.crossover));
+        }
+        assert!((0.0..=1.0).contains(&p.success));
+        // configured rates are restored
+        assert_eq!(p.rates(), stat[0].4);
+        // a constant schedule keeps the search as it was
+        let mut p1 = Population::new(settings(20, 2, 0.2, Some(4)));
+        let mut p2 = Population::new(settings(20, 2, 0.2, Some(4)));
+        p2.set_schedule("transposition", Schedule::Constant);
+        assert!(p1.search(&fe, 5) == p2.search(&fe, 5));
+    }
+
+    #[test]
+    fn checkpoint_test() {
+        use std::sync::Mutex;
+        let fe = sample_evaluator();
+        let population = |seed: u64, saved: &Arc<Mutex<Vec<String>>>| {
+            let mut p = Population::new(settings(20, 2, 0.4, Some(seed)));
+            p.set_schedule("mutation", Schedule::Stagnation(0.8));
+            p.set_operator_rates(OperatorRates { inversion: 0.2, ..OperatorRates::default() });
+            p.set_target_fitness(None);
+            let saved = saved.clone();
+            p.set_checkpoints(3, Box::new(move |c| saved.lock().unwrap().push(toml::to_string(c).unwrap())));
+            p
+        };
+        let saved = Arc::new(Mutex::new(Vec::new()));
+        let mut p1 = population(21, &saved);
+        let s1 = p1.search(&fe, 10);
+        let saved = saved.lock().unwrap().clone();
+        assert_eq!(saved.len(), 3);
+        // making checkpoints does not change the search
+        let mut p0 = population(21, &Arc::new(Mutex::new(Vec::new())));
+        p0.set_checkpoints(0, Box::new(|_| {}));
+        assert!(p0.search(&fe, 10) == s1);
+        // resumed searches end like the uninterrupted one
+        for c in &saved {
+            let checkpoint: Checkpoint = toml::from_str(c).unwrap();
+            let g = checkpoint.generation;
+            let mut p2 = population(22, &Arc::new(Mutex::new(Vec::new())));
+            let s2 = p2.resume(&fe, checkpoint, 10 - g).unwrap();
+            assert!(s1 == s2);
+            assert_eq!(p2.rates(), p1.rates());
+            for (c1, c2) in p1.chromosomes.iter().zip(&p2.chromosomes) {
+                assert_eq!(c1.k_string(), c2.k_string());
+            }
+        }
+        let checkpoint: Checkpoint = toml::from_str(&saved[0]).unwrap();
+        let mut p = Population::new(settings(10, 2, 0.4, None));
+        assert!(p.resume(&fe, checkpoint, 5).is_none());
+    }
+
+    #[test]
+    fn odd_size_and_crossover_test() {
+        let fe = sample_evaluator();
+        for (mode, p) in [(CrossoverMode::Free, 0.0), (CrossoverMode::Genes, 0.7), (CrossoverMode::InGene, 1.0)] {
+            let mut pop = Population::new(settings(21, 3, 0.2, Some(9)));
+            pop.set_elite_count(0);
+            pop.set_crossover(mode, p);
+            pop.search(&fe, 5);
+            assert_eq!(pop.chromosomes.len(), 21);
+        }
+        let mut pop = Population::new(settings(21, 3, 0.2, Some(9)));
+        pop.set_crossover(CrossoverMode::Genes, 3.0);
+        assert_eq!(pop.crossover_probability, 1.0);
+    }
+
+    #[test]
+    fn pareto_search_test() {
+        use crate::pareto::{dominates, Criterion, TradingCriteria};
+        let tc = TradingCriteria::new(sample_evaluator(), vec![Criterion::Return, Criterion::Size]);
+        let mut p1 = Population::new(settings(20, 2, 0.2, Some(3)));
+        let mut p2 = Population::new(settings(20, 2, 0.2, Some(3)));
+        let front = p1.search_pareto(&tc, 5);
+        assert!(!front.is_empty());
+        assert_eq!(p1.chromosomes.len(), 20);
+        for (k, objs, i) in &front {
+            assert_eq!(*k, p1.chromosomes[*i].k_string());
+            assert_eq!(*objs, p1.chromosomes[*i].objectives(2, &tc));
+            assert!(p1.chromosomes.iter().all(|c| !dominates(&c.objectives(2, &tc), objs)));
+        }
+        assert!(front == p2.search_pareto(&tc, 5));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_evaluation_test() {
+        let fe = sample_evaluator();
+        let mut p1 = Population::new(settings(20, 2, 0.2, Some(7)));
+        let mut p2 = Population::new(settings(20, 2, 0.2, Some(7)));
+        p2.set_threads(4);
+        assert!(p1.search(&fe, 10) == p2.search(&fe, 10));
+    }
+}
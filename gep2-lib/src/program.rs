@@ -0,0 +1,289 @@
+use crate::codons::Codon;
+use crate::delay_line::DelayLine;
+use crate::feedback::Feedback;
+use crate::functions::Primitive;
+use crate::linking_function::{LinkingFunction, Majority};
+use crate::state_functions::{Memory, StateFunction};
+use crate::terminal::Terminal;
+
+// single step of a compiled gene; genes are stored in postfix order
+pub enum Instruction<'a> {
+    Terminal(&'a Terminal),
+    Feedback(&'a Feedback),
+    // value of a random numerical constant
+    Constant(f32),
+    Function(&'a Primitive),
+    // state function with index of its memory
+    StateFunction(&'a StateFunction, usize),
+}
+
+// compiled chromosome: built once, then evaluated row by row without allocations
+pub struct Program<'a> {
+    genes: Vec<Vec<Instruction<'a>>>,
+    stack: Vec<f32>,
+    state: Vec<Memory>,
+    outputs: Vec<f32>,
+    // homeotic genes (GEP-ADF): their terminals read outputs of the genes above,
+    // the linking function combines their outputs instead
+    cells: Vec<Vec<Instruction<'a>>>,
+    cell_outputs: Vec<f32>,
+    linker: &'a dyn LinkingFunction,
+    weights: &'a [f32],
+}
+
+impl<'a> Program<'a> {
+    // genes linked by majority vote
+    pub fn new(codons: &'a [Codon], gene_length: usize, nbr_of_genes: usize) -> Self {
+        Self::with_linker(codons, gene_length, nbr_of_genes, &Majority, &[])
+    }
+
+    pub fn with_linker(codons: &'a [Codon], gene_length: usize, nbr_of_genes: usize,
+                       linker: &'a dyn LinkingFunction, weights: &'a [f32]) -> Self {
+        Self::with_constants(codons, gene_length, nbr_of_genes, linker, weights, &[])
+    }
+
+    // constants[g] holds values of the `?` codons of gene g in order of their position
+    // (missing values are zero)
+    pub fn with_constants(codons: &'a [Codon], gene_length: usize, nbr_of_genes: usize,
+                          linker: &'a dyn LinkingFunction, weights: &'a [f32], constants: &[Vec<f32>]) -> Self {
+        let mut genes = Vec::with_capacity(nbr_of_genes);
+        let mut state = Vec::new();
+        let mut depth = 0;
+        for i in 0..nbr_of_genes {
+            let values = constants.get(i).map(|c| c.as_slice()).unwrap_or(&[]);
+            let code = Self::compile_gene(&codons[i * gene_length..(i + 1) * gene_length], values, &mut state);
+            if code.len() > depth { depth = code.len() }
+            genes.push(code);
+        }
+        Program {
+            genes,
+            stack: Vec::with_capacity(depth),
+            state,
+            outputs: vec![0.0; nbr_of_genes],
+            cells: Vec::new(),
+            cell_outputs: Vec::new(),
+            linker,
+            weights,
+        }
+    }
+
+    // homeotic genes, terminal i of a homeotic gene is the output of gene i
+    pub fn with_homeotic(mut self, codons: &'a [Codon], gene_length: usize, nbr_of_genes: usize) -> Self {
+        for i in 0..nbr_of_genes {
+            let code = Self::compile_gene(&codons[i * gene_length..(i + 1) * gene_length], &[], &mut self.state);
+            self.stack.reserve(code.len());
+            self.cells.push(code);
+        }
+        self.cell_outputs = vec![0.0; nbr_of_genes];
+        self
+    }
+
+    fn compile_gene(gene: &'a [Codon], values: &[f32], state: &mut Vec<Memory>) -> Vec<Instruction<'a>> {
+        // first pass: position of the first argument of every expressed codon (Karva notation)
+        // and value of every expressed constant
+        let mut first_arg_positions = Vec::with_capacity(gene.len());
+        let mut constants = Vec::with_capacity(gene.len());
+        let mut first_arg_position = 1;
+        let mut pos = 0;
+        while pos < first_arg_position {
+            first_arg_positions.push(first_arg_position);
+            if let Codon::Constant = gene[pos] {
+                constants.push(values.get(constants.len()).cloned().unwrap_or(0.0));
+            }
+            first_arg_position += usize::from(gene[pos].get_arity());
+            pos += 1;
+        }
+        // second pass: emit expressed codons in postfix order
+        let mut code = Vec::with_capacity(first_arg_positions.len());
+        Self::emit(gene, 0, &first_arg_positions, &constants, state, &mut code);
+        code
+    }
+
+    fn emit(gene: &'a [Codon], pos: usize, first_arg_positions: &[usize], constants: &[f32],
+            state: &mut Vec<Memory>, code: &mut Vec<Instruction<'a>>) {
+        let c = &gene[pos];
+        let first = first_arg_positions[pos];
+        for i in 0..usize::from(c.get_arity()) {
+            Self::emit(gene, first + i, first_arg_positions, constants, state, code);
+        }
+        code.push(match c {
+            Codon::Terminal(ref t) => Instruction::Terminal(t),
+            Codon::Feedback(ref f) => Instruction::Feedback(f),
+            // constants are numbered in order of position
+            Codon::Constant => {
+                let k = gene[..pos].iter().filter(|c| matches!(c, Codon::Constant)).count();
+                Instruction::Constant(constants[k])
+            }
+            Codon::Function(ref f) => Instruction::Function(&f.fd),
+            Codon::StateFunction(ref f) => {
+                state.push(f.memory());
+                Instruction::StateFunction(f, state.len() - 1)
+            }
+        });
+    }
+
+    // number of expressed codons
+    pub fn size(&self) -> usize {
+        self.genes.iter().chain(&self.cells).map(|g| g.len()).sum()
+    }
+
+    // clears memories of state functions; evaluators call it before every run over the data,
+    // so results do not depend on previous runs
+    pub fn reset(&mut self) {
+        for m in self.state.iter_mut() {
+            m.reset();
+        }
+        self.stack.clear();
+    }
+
+    pub fn eval(&mut self, args: &Vec<f32>, delay_line: &DelayLine) -> f32 {
+        for (g, code) in self.genes.iter().enumerate() {
+            self.outputs[g] = Self::run(code, &mut self.stack, &mut self.state, args, delay_line);
+        }
+        if self.cells.is_empty() {
+            return self.linker.evaluate(&self.outputs, self.weights);
+        }
+        for (c, code) in self.cells.iter().enumerate() {
+            self.cell_outputs[c] = Self::run(code, &mut self.stack, &mut self.state, &self.outputs, delay_line);
+        }
+        self.linker.evaluate(&self.cell_outputs, self.weights)
+    }
+
+    fn run(code: &[Instruction], stack: &mut Vec<f32>, state: &mut [Memory], args: &Vec<f32>, delay_line: &DelayLine) -> f32 {
+        stack.clear();
+        for instruction in code {
+            let v = match *instruction {
+                Instruction::Terminal(t) => t.eval(args),
+                Instruction::Feedback(f) => f.eval(delay_line),
+                Instruction::Constant(v) => v,
+                Instruction::Function(fd) => {
+                    // arguments are the topmost `arity` values, first argument deepest
+                    let base = stack.len() - usize::from(fd.arity);
+                    let ret = (fd.op)(&stack[base..]);
+                    stack.truncate(base);
+                    ret
+                }
+                Instruction::StateFunction(f, slot) => {
+                    let x = stack.pop().unwrap();
+                    f.eval(x, &mut state[slot])
+                }
+            };
+            stack.push(v);
+        }
+        stack.pop().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::{Function, Primitive, EXT_FREGISTRY, FREGISTRY};
+    use crate::state_functions::{StateFunction, SFREGISTRY};
+
+    #[test]
+    fn karva_order_test() {
+        // Max(Neg(args[1]), args[0]); last codon is not expressed
+        let codons = vec![
+            Codon::Function(Function::new(Primitive::builtin(&FREGISTRY[1]))),
+            Codon::Function(Function::new(Primitive::builtin(&FREGISTRY[3]))),
+            Codon::Terminal(Terminal::new(0)),
+            Codon::Terminal(Terminal::new(1)),
+            Codon::Terminal(Terminal::new(2)),
+        ];
+        let dl = DelayLine::new(1);
+        let mut p = Program::new(&codons, 5, 1);
+        assert_eq!(p.genes[0].len(), 4);
+        assert_eq!(p.size(), 4);
+        assert_eq!(p.eval(&vec![-0.5, 1.0, 1.0], &dl), -1.0);
+        assert_eq!(p.eval(&vec![0.5, 1.0, -1.0], &dl), 1.0);
+    }
+
+    #[test]
+    fn state_slot_test() {
+        // two genes: Delay(args[0]) and Avg(args[0], args[1])
+        let codons = vec![
+            Codon::StateFunction(StateFunction::new(&SFREGISTRY[0])),
+            Codon::Terminal(Terminal::new(0)),
+            Codon::Terminal(Terminal::new(1)),
+            Codon::Function(Function::new(Primitive::builtin(&FREGISTRY[2]))),
+            Codon::Terminal(Terminal::new(0)),
+            Codon::Terminal(Terminal::new(1)),
+        ];
+        let dl = DelayLine::new(1);
+        let mut p = Program::new(&codons, 3, 2);
+        assert_eq!(p.state.len(), 1);
+        // Delay starts at 0, Avg is positive -> Maj is 1
+        assert_eq!(p.eval(&vec![1.0, 0.0], &dl), 1.0);
+        // Delay returns 1, Avg is -1 -> sum is zero, signum(0.0) is 1
+        assert_eq!(p.eval(&vec![-1.0, -1.0], &dl), 1.0);
+        // Delay returns -1, Avg is negative
+        assert_eq!(p.eval(&vec![0.0, -1.0], &dl), -1.0);
+    }
+
+    #[test]
+    fn constants_test() {
+        // Max(?, Min(args[0], ?)) with constants 0.5 and -0.25
+        let codons = vec![
+            Codon::Function(Function::new(Primitive::builtin(&FREGISTRY[1]))),
+            Codon::Constant,
+            Codon::Function(Function::new(Primitive::builtin(&FREGISTRY[0]))),
+            Codon::Terminal(Terminal::new(0)),
+            Codon::Constant,
+            Codon::Constant,
+        ];
+        let dl = DelayLine::new(1);
+        let mut p = Program::with_constants(&codons, 6, 1, &crate::linking_function::Average, &[], &[vec![0.5, -0.25, 1.0]]);
+        assert_eq!(p.eval(&vec![1.0], &dl), 0.5);
+        let mut p = Program::with_constants(&codons, 6, 1, &crate::linking_function::Average, &[], &[vec![-0.5, -0.25]]);
+        assert_eq!(p.eval(&vec![1.0], &dl), -0.25);
+        assert_eq!(p.eval(&vec![-1.0], &dl), -0.5);
+        // missing values are zero
+        let mut p = Program::with_constants(&codons, 6, 1, &crate::linking_function::Average, &[], &[]);
+        assert_eq!(p.eval(&vec![1.0], &dl), 0.0);
+    }
+
+    #[test]
+    fn homeotic_test() {
+        // genes args[0] and Neg(args[1]), homeotic genes Max(0, 1) and Min(0, 1) averaged
+        let codons = vec![
+            Codon::Terminal(Terminal::new(0)),
+            Codon::Terminal(Terminal::new(0)),
+            Codon::Function(Function::new(Primitive::builtin(&FREGISTRY[3]))),
+            Codon::Terminal(Terminal::new(1)),
+        ];
+        let homeotic = vec![
+            Codon::Function(Function::new(Primitive::builtin(&FREGISTRY[1]))),
+            Codon::Terminal(Terminal::new(0)),
+            Codon::Terminal(Terminal::new(1)),
+            Codon::Function(Function::new(Primitive::builtin(&FREGISTRY[0]))),
+            Codon::Terminal(Terminal::new(0)),
+            Codon::Terminal(Terminal::new(1)),
+        ];
+        let dl = DelayLine::new(1);
+        let mut p = Program::with_linker(&codons, 2, 2, &crate::linking_function::Average, &[]).with_homeotic(&homeotic, 3, 2);
+        assert_eq!(p.size(), 3 + 6);
+        // outputs of the genes 0.5 and -1, of the homeotic genes 0.5 and -1
+        assert_eq!(p.eval(&vec![0.5, 1.0], &dl), -0.25);
+        // outputs 1 and 0.5 -> 1 and 0.5
+        assert_eq!(p.eval(&vec![1.0, -0.5], &dl), 0.75);
+    }
+
+    #[test]
+    fn ternary_test() {
+        // If(args[0], Neg(args[1]), args[2])
+        let codons = vec![
+            Codon::Function(Function::new(Primitive::builtin(&EXT_FREGISTRY[10]))),
+            Codon::Terminal(Terminal::new(0)),
+            Codon::Function(Function::new(Primitive::builtin(&FREGISTRY[3]))),
+            Codon::Terminal(Terminal::new(2)),
+            Codon::Terminal(Terminal::new(1)),
+            Codon::Terminal(Terminal::new(0)),
+            Codon::Terminal(Terminal::new(0)),
+        ];
+        let dl = DelayLine::new(1);
+        let mut p = Program::new(&codons, 7, 1);
+        assert_eq!(p.genes[0].len(), 5);
+        assert_eq!(p.eval(&vec![0.5, 0.5, 0.5], &dl), -1.0);
+        assert_eq!(p.eval(&vec![-0.5, 0.5, 0.5], &dl), 1.0);
+    }
+}
@@ -0,0 +1,99 @@
+// how an operator rate changes from one generation to the next; rates are kept in [0,1]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Schedule {
+    Constant,
+    // linear change from the initial rate to the given one over the whole search
+    Linear(f32),
+    // the rate is multiplied by the factor every generation
+    Exponential(f32),
+    // the rate is multiplied by the factor after every generation without improvement of
+    // the best fitness and reset to the initial rate on improvement
+    Stagnation(f32),
+    // 1/5 success rule: the rate is multiplied by the factor if more than a fifth of the offspring
+    // are better than their parents and divided by it if less
+    OneFifth(f32),
+}
+
+// progress of the search after a generation
+pub struct Progress {
+    // index of the finished generation and number of generations of the search
+    pub generation: usize,
+    pub generations: usize,
+    // the best fitness improved
+    pub improved: bool,
+    // fraction of offspring better than their parents
+    pub success: f32,
+}
+
+impl Schedule {
+    // "Const", "Lin<final rate>", "Exp<factor>", "Stag<factor>" or "Fifth<factor>", e.g. "Exp0.95"
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        if symbol == "Const" {
+            Some(Schedule::Constant)
+        } else if let Some(r) = symbol.strip_prefix("Lin") {
+            Some(Schedule::Linear(r.parse().ok()?))
+        } else if let Some(f) = symbol.strip_prefix("Exp") {
+            Some(Schedule::Exponential(f.parse().ok()?))
+        } else if let Some(f) = symbol.strip_prefix("Stag") {
+            Some(Schedule::Stagnation(f.parse().ok()?))
+        } else if let Some(f) = symbol.strip_prefix("Fifth") {
+            let f: f32 = f.parse().ok()?;
+            if f > 0.0 {Some(Schedule::OneFifth(f))} else {None}
+        } else {
+            None
+        }
+    }
+
+    // rate for the next generation
+    pub fn next(&self, initial: f32, rate: f32, progress: &Progress) -> f32 {
+        let r = match *self {
+            Schedule::Constant => rate,
+            Schedule::Linear(last) => {
+                let t = (progress.generation + 1) as f32 / progress.generations.max(1) as f32;
+                initial + (last - initial) * t.min(1.0)
+            }
+            Schedule::Exponential(f) => rate * f,
+            Schedule::Stagnation(f) => if progress.improved {initial} else {rate * f},
+            Schedule::OneFifth(f) => {
+                if progress.success > 0.2 {rate * f} else if progress.success < 0.2 {rate / f} else {rate}
+            }
+        };
+        r.clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(generation: usize, improved: bool, success: f32) -> Progress {
+        Progress { generation, generations: 10, improved, success }
+    }
+
+    #[test]
+    fn schedules_test() {
+        let p = progress(4, false, 0.5);
+        assert_eq!(Schedule::Constant.next(0.2, 0.3, &p), 0.3);
+        assert!((Schedule::Linear(0.0).next(0.2, 0.2, &p) - 0.1).abs() < 1e-6);
+        assert_eq!(Schedule::Linear(0.0).next(0.2, 0.2, &progress(9, false, 0.0)), 0.0);
+        assert_eq!(Schedule::Exponential(0.5).next(0.2, 0.3, &p), 0.15);
+        assert_eq!(Schedule::Exponential(5.0).next(0.2, 0.3, &p), 1.0);
+        assert_eq!(Schedule::Stagnation(2.0).next(0.2, 0.3, &p), 0.6);
+        assert_eq!(Schedule::Stagnation(2.0).next(0.2, 0.3, &progress(4, true, 0.0)), 0.2);
+        assert_eq!(Schedule::OneFifth(2.0).next(0.2, 0.3, &p), 0.6);
+        assert_eq!(Schedule::OneFifth(2.0).next(0.2, 0.3, &progress(4, false, 0.1)), 0.15);
+        assert_eq!(Schedule::OneFifth(2.0).next(0.2, 0.3, &progress(4, false, 0.2)), 0.3);
+    }
+
+    #[test]
+    fn from_symbol_test() {
+        assert_eq!(Schedule::from_symbol("Const"), Some(Schedule::Constant));
+        assert_eq!(Schedule::from_symbol("Lin0.05"), Some(Schedule::Linear(0.05)));
+        assert_eq!(Schedule::from_symbol("Exp0.95"), Some(Schedule::Exponential(0.95)));
+        assert_eq!(Schedule::from_symbol("Stag1.5"), Some(Schedule::Stagnation(1.5)));
+        assert_eq!(Schedule::from_symbol("Fifth1.2"), Some(Schedule::OneFifth(1.2)));
+        assert_eq!(Schedule::from_symbol("Fifth0"), None);
+        assert_eq!(Schedule::from_symbol("Exp"), None);
+        assert_eq!(Schedule::from_symbol("Step"), None);
+    }
+}
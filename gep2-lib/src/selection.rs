@@ -0,0 +1,187 @@
+use rand::{Rng, RngCore};
+
+// chooses parents of the next generation by fitness (higher is better)
+pub trait Selection: Send + Sync {
+    fn symbol(&self) -> String;
+    // indexes of n parents
+    fn select(&self, fitness: &[f32], n: usize, rng: &mut dyn RngCore) -> Vec<usize>;
+}
+
+// index drawn with probability proportional to weight, cumulative holds running sums of weights;
+// uniform if all weights are zero
+fn spin<R: Rng + ?Sized>(cumulative: &[f32], rng: &mut R) -> usize {
+    let total = cumulative[cumulative.len() - 1];
+    if total <= 0.0 { return rng.gen::<usize>() % cumulative.len() }
+    let x = rng.gen_range(0.0..total);
+    cumulative.partition_point(|c| *c <= x).min(cumulative.len() - 1)
+}
+
+fn running_sums(weights: impl Iterator<Item = f32>) -> Vec<f32> {
+    let mut sum = 0.0;
+    weights.map(|w| { sum += w.max(0.0); sum }).collect()
+}
+
+// indexes ordered from the best to the worst
+fn by_fitness(fitness: &[f32]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..fitness.len()).collect();
+    order.sort_by(|a, b| fitness[*b].partial_cmp(&fitness[*a]).unwrap_or(std::cmp::Ordering::Equal));
+    order
+}
+
+// fitness-proportional (roulette wheel) selection
+pub struct Roulette;
+impl Selection for Roulette {
+    fn symbol(&self) -> String { "Roulette".to_string() }
+    fn select(&self, fitness: &[f32], n: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        let wheel = running_sums(fitness.iter().cloned());
+        (0..n).map(|_| spin(&wheel, rng)).collect()
+    }
+}
+
+// best of k uniformly drawn chromosomes
+pub struct Tournament {
+    pub size: usize,
+}
+impl Selection for Tournament {
+    fn symbol(&self) -> String { format!("Tour{}", self.size) }
+    fn select(&self, fitness: &[f32], n: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        (0..n).map(|_| {
+            let mut best = rng.gen::<usize>() % fitness.len();
+            for _ in 1..self.size {
+                let i = rng.gen::<usize>() % fitness.len();
+                if fitness[i] > fitness[best] { best = i }
+            }
+            best
+        }).collect()
+    }
+}
+
+// linear ranking: the best chromosome is `pressure` times (1..2) more likely chosen than
+// an average one, the worst 2 - pressure times
+pub struct LinearRank {
+    pub pressure: f32,
+}
+impl Selection for LinearRank {
+    fn symbol(&self) -> String { format!("Rank{}", self.pressure) }
+    fn select(&self, fitness: &[f32], n: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        let order = by_fitness(fitness);
+        let len = order.len();
+        let s = self.pressure;
+        // weight of the best (rank 0) is s, of the worst 2 - s
+        let wheel = running_sums((0..len).map(|r| {
+            if len == 1 {1.0} else {s - 2.0 * (s - 1.0) * r as f32 / (len - 1) as f32}
+        }));
+        (0..n).map(|_| order[spin(&wheel, rng)]).collect()
+    }
+}
+
+// uniform choice among the best `fraction` of the population (at least one)
+pub struct Truncation {
+    pub fraction: f32,
+}
+impl Selection for Truncation {
+    fn symbol(&self) -> String { format!("Trunc{}", self.fraction) }
+    fn select(&self, fitness: &[f32], n: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        let order = by_fitness(fitness);
+        let top = ((order.len() as f32 * self.fraction).ceil() as usize).clamp(1, order.len());
+        (0..n).map(|_| order[rng.gen::<usize>() % top]).collect()
+    }
+}
+
+// "Roulette", "Tour3" (tournament size, none - 2), "Rank1.5" (selection pressure in [1,2],
+// none - 1.5) or "Trunc0.3" (fraction in (0,1], none - 0.5); out of range parameters are clamped
+pub fn from_symbol(symbol: &str) -> Option<Box<dyn Selection>> {
+    fn param<T: std::str::FromStr>(s: &str, default: T) -> Option<T> {
+        if s.is_empty() {Some(default)} else {s.parse().ok()}
+    }
+    if symbol == "Roulette" {
+        Some(Box::new(Roulette))
+    } else if let Some(k) = symbol.strip_prefix("Tour") {
+        Some(Box::new(Tournament { size: param(k, 2usize)?.max(1) }))
+    } else if let Some(s) = symbol.strip_prefix("Rank") {
+        Some(Box::new(LinearRank { pressure: param(s, 1.5f32)?.clamp(1.0, 2.0) }))
+    } else if let Some(f) = symbol.strip_prefix("Trunc") {
+        let f = param(f, 0.5f32)?;
+        Some(Box::new(Truncation { fraction: if f > 0.0 && f <= 1.0 {f} else {1.0} }))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha12Rng;
+
+    const FITNESS: [f32; 5] = [0.1, 0.0, 0.6, 0.3, 0.0];
+
+    fn counts(s: &dyn Selection, fitness: &[f32]) -> Vec<usize> {
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let mut cnt = vec![0; fitness.len()];
+        for i in s.select(fitness, 10000, &mut rng) {
+            cnt[i] += 1;
+        }
+        cnt
+    }
+
+    #[test]
+    fn roulette_test() {
+        let cnt = counts(&Roulette, &FITNESS);
+        assert_eq!(cnt[1] + cnt[4], 0);
+        assert!(cnt[2] > 5500 && cnt[2] < 6500);
+        assert!(cnt[0] > 700 && cnt[0] < 1300);
+        // tiny fitness is kept, zero total is uniform
+        assert!(counts(&Roulette, &[0.00001, 0.0])[0] == 10000);
+        assert!(counts(&Roulette, &[0.0, 0.0])[1] > 4000);
+    }
+
+    #[test]
+    fn tournament_test() {
+        assert_eq!(counts(&Tournament { size: 1 }, &FITNESS).iter().filter(|c| **c > 1500).count(), 5);
+        let cnt = counts(&Tournament { size: 50 }, &FITNESS);
+        assert_eq!(cnt[2], 10000);
+        let cnt = counts(&Tournament { size: 2 }, &FITNESS);
+        // the worst two win only against each other: 4/25 of draws
+        assert!(cnt[1] + cnt[4] > 1300 && cnt[1] + cnt[4] < 1900);
+        assert!(cnt[2] > cnt[3] && cnt[3] > cnt[0]);
+    }
+
+    #[test]
+    fn linear_rank_test() {
+        let cnt = counts(&LinearRank { pressure: 2.0 }, &FITNESS);
+        // weights 2, 1.5, 1, 0.5, 0 by rank: 2, 3, 0, then 1 and 4 (equal fitness)
+        assert!(cnt[2] > 3500 && cnt[2] < 4500);
+        assert!(cnt[3] > 2500 && cnt[3] < 3500);
+        assert!(cnt[1] + cnt[4] < 1300);
+        let cnt = counts(&LinearRank { pressure: 1.0 }, &FITNESS);
+        assert!(cnt.iter().all(|c| *c > 1700 && *c < 2300));
+    }
+
+    #[test]
+    fn truncation_test() {
+        let cnt = counts(&Truncation { fraction: 0.4 }, &FITNESS);
+        assert_eq!(cnt[0] + cnt[1] + cnt[4], 0);
+        assert!(cnt[2] > 4500 && cnt[3] > 4500);
+        assert_eq!(counts(&Truncation { fraction: 0.01 }, &FITNESS)[2], 10000);
+    }
+
+    #[test]
+    fn any_rng_test() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let parents = Tournament { size: 50 }.select(&FITNESS, 10, &mut rng);
+        assert!(parents.iter().all(|i| *i == 2));
+    }
+
+    #[test]
+    fn from_symbol_test() {
+        for s in ["Roulette", "Tour3", "Rank1.2", "Trunc0.3"] {
+            assert_eq!(from_symbol(s).unwrap().symbol(), s);
+        }
+        assert_eq!(from_symbol("Tour").unwrap().symbol(), "Tour2");
+        assert_eq!(from_symbol("Rank3").unwrap().symbol(), "Rank2");
+        assert_eq!(from_symbol("Trunc").unwrap().symbol(), "Trunc0.5");
+        assert!(from_symbol("Tourx").is_none());
+        assert!(from_symbol("Best").is_none());
+    }
+}
@@ -1,45 +1,165 @@
-use std::cell::Cell;
+use crate::delay_line::DelayLine;
 
 pub struct StateFunctionDescription{
     pub symbol : &'static str,
-    pub op : fn(f32, f32) -> f32,
+    // (current input, previous inputs, previous output) -> output
+    pub op : fn(f32, &DelayLine, f32) -> f32,
+    // window length is a parameter of the codon; otherwise it is 1
+    pub windowed: bool,
 }
-pub fn delay(_curr: f32, prev: f32) -> f32{ prev }
-pub fn ma2(curr: f32, prev: f32) -> f32{ 0.5*(curr + prev) }
-pub fn diff(curr: f32, prev: f32) -> f32{
-    let temp = curr - prev;
-    if temp > 1.0 {1.0} else if temp < -1.0 {-1.0} else {temp}
+
+fn clip(x: f32) -> f32{
+    x.clamp(-1.0, 1.0)
+}
+
+pub fn delay(_curr: f32, prev: &DelayLine, _out: f32) -> f32{ prev.get_shifted_back(0) }
+pub fn ma2(curr: f32, prev: &DelayLine, _out: f32) -> f32{ 0.5*(curr + prev.get_shifted_back(0)) }
+pub fn diff(curr: f32, prev: &DelayLine, _out: f32) -> f32{
+    clip(curr - prev.get_shifted_back(0))
 }
 
 pub const SFREGISTRY: &'static [StateFunctionDescription] = &[
     StateFunctionDescription {
         op: delay,
-        symbol: "Delay"
+        symbol: "Delay",
+        windowed: false
     },
     StateFunctionDescription {
         op: ma2,
-        symbol: "Ma2"
+        symbol: "Ma2",
+        windowed: false
     },
     StateFunctionDescription {
         op: diff,
-        symbol: "Diff"
+        symbol: "Diff",
+        windowed: false
     },
 ];
 
 pub const SFN_NUM: usize = 3;
 
+// windowed functions of window N keep N previous inputs;
+// rolling statistics use the current input and N-1 previous ones
+fn rolling(curr: f32, prev: &DelayLine) -> impl Iterator<Item = f32> + '_ {
+    std::iter::once(curr).chain((0..prev.capacity - 1).map(move |k| prev.get_shifted_back(k)))
+}
+
+// input N steps ago
+pub fn lag(_curr: f32, prev: &DelayLine, _out: f32) -> f32{
+    prev.get_shifted_back(prev.capacity - 1)
+}
+pub fn sma(curr: f32, prev: &DelayLine, _out: f32) -> f32{
+    rolling(curr, prev).sum::<f32>() / prev.capacity as f32
+}
+// alpha = 2/(N+1), starts from 0 like the other memories
+pub fn ema(curr: f32, prev: &DelayLine, out: f32) -> f32{
+    out + 2.0 / (prev.capacity as f32 + 1.0) * (curr - out)
+}
+pub fn rmin(curr: f32, prev: &DelayLine, _out: f32) -> f32{
+    rolling(curr, prev).fold(curr, f32::min)
+}
+pub fn rmax(curr: f32, prev: &DelayLine, _out: f32) -> f32{
+    rolling(curr, prev).fold(curr, f32::max)
+}
+pub fn rsum(curr: f32, prev: &DelayLine, _out: f32) -> f32{
+    clip(rolling(curr, prev).sum::<f32>())
+}
+pub fn mom(curr: f32, prev: &DelayLine, _out: f32) -> f32{
+    clip(curr - prev.get_shifted_back(prev.capacity - 1))
+}
+// z-score of the current input, scaled so that 2 standard deviations map to 1
+pub fn zscore(curr: f32, prev: &DelayLine, _out: f32) -> f32{
+    let n = prev.capacity as f32;
+    let mean = rolling(curr, prev).sum::<f32>() / n;
+    let var = rolling(curr, prev).map(|x| (x - mean) * (x - mean)).sum::<f32>() / n;
+    if var <= 0.0 {0.0} else {clip(0.5 * (curr - mean) / var.sqrt())}
+}
+
+// additional state functions with configurable window, not used unless selected explicitly
+pub const EXT_SFREGISTRY: &'static [StateFunctionDescription] = &[
+    StateFunctionDescription {
+        op: lag,
+        symbol: "Lag",
+        windowed: true
+    },
+    StateFunctionDescription {
+        op: sma,
+        symbol: "Sma",
+        windowed: true
+    },
+    StateFunctionDescription {
+        op: ema,
+        symbol: "Ema",
+        windowed: true
+    },
+    StateFunctionDescription {
+        op: rmin,
+        symbol: "RMin",
+        windowed: true
+    },
+    StateFunctionDescription {
+        op: rmax,
+        symbol: "RMax",
+        windowed: true
+    },
+    StateFunctionDescription {
+        op: rsum,
+        symbol: "RSum",
+        windowed: true
+    },
+    StateFunctionDescription {
+        op: mom,
+        symbol: "Mom",
+        windowed: true
+    },
+    StateFunctionDescription {
+        op: zscore,
+        symbol: "ZSc",
+        windowed: true
+    },
+];
+
+// memory of a state function codon: previous inputs and previous output
+pub struct Memory{
+    pub inputs: DelayLine,
+    pub output: f32,
+}
+impl Memory{
+    pub fn new(window: usize) -> Self{
+        Memory{ inputs: DelayLine::new(window), output: 0.0 }
+    }
+    pub fn reset(&mut self){
+        self.inputs.reset();
+        self.output = 0.0;
+    }
+}
+
 pub struct StateFunction{
     pub fd: &'static StateFunctionDescription,
-    pub buf: Cell<f32>,
-    pub first_arg_position:Cell<usize>
+    pub window: usize,
+    // symbol with window length, e.g. "Sma10"
+    pub symbol: String,
 }
 impl StateFunction{
     pub fn new(fd: &'static StateFunctionDescription) -> Self{
-        StateFunction{ fd, buf: Cell::new(0.0), first_arg_position:Cell::new(0) }
+        Self::with_window(fd, 1)
+    }
+    pub fn with_window(fd: &'static StateFunctionDescription, window: usize) -> Self{
+        if fd.windowed {
+            let window = if window < 1 {1} else {window};
+            StateFunction{ fd, window, symbol: format!("{}{}", fd.symbol, window) }
+        } else {
+            StateFunction{ fd, window: 1, symbol: fd.symbol.to_string() }
+        }
     }
-    pub fn eval(&self, curr:f32)-> f32{
-        let ret = (self.fd.op)(curr,self.buf.get());
-        self.buf.set(curr);
+    pub fn memory(&self) -> Memory{
+        Memory::new(self.window)
+    }
+    // memory lives outside of the codon, so chromosomes stay immutable (and Sync) during evaluation
+    pub fn eval(&self, curr:f32, mem: &mut Memory)-> f32{
+        let ret = (self.fd.op)(curr, &mem.inputs, mem.output);
+        mem.inputs.push(curr);
+        mem.output = ret;
         ret
     }
 }
@@ -50,19 +170,21 @@ mod tests {
 
     #[test]
     fn delay_test() {
-        let mut d = StateFunction::new(&SFREGISTRY[0]);
-        let x1 = d.eval(1.0);
-        let x2 = d.eval(0.0);
+        let d = StateFunction::new(&SFREGISTRY[0]);
+        let mut buf = d.memory();
+        let x1 = d.eval(1.0, &mut buf);
+        let x2 = d.eval(0.0, &mut buf);
         assert_eq!(0.0, x1);
         assert_eq!(1.0, x2);
     }
 
     #[test]
     fn ma2_test() {
-        let mut d = StateFunction::new(&SFREGISTRY[1]);
-        let x1 = d.eval(1.0); // 0.5
-        let x2 = d.eval(1.0); // 1
-        let x3 = d.eval(-1.0); // 0
+        let d = StateFunction::new(&SFREGISTRY[1]);
+        let mut buf = d.memory();
+        let x1 = d.eval(1.0, &mut buf); // 0.5
+        let x2 = d.eval(1.0, &mut buf); // 1
+        let x3 = d.eval(-1.0, &mut buf); // 0
         assert_eq!(0.5, x1);
         assert_eq!(1.0, x2);
         assert_eq!(0.0, x3);
@@ -70,12 +192,69 @@ mod tests {
 
     #[test]
     fn diff_test() {
-        let mut d = StateFunction::new(&SFREGISTRY[2]);
-        let x1 = d.eval(1.0); // 1
-        let x2 = d.eval(1.0); // 0
-        let x3 = d.eval(-1.0); // -1
+        let d = StateFunction::new(&SFREGISTRY[2]);
+        let mut buf = d.memory();
+        let x1 = d.eval(1.0, &mut buf); // 1
+        let x2 = d.eval(1.0, &mut buf); // 0
+        let x3 = d.eval(-1.0, &mut buf); // -1
         assert_eq!(1.0, x1);
         assert_eq!(0.0, x2);
         assert_eq!(-1.0, x3);
     }
+
+    fn ext(symbol: &str, window: usize) -> StateFunction {
+        StateFunction::with_window(EXT_SFREGISTRY.iter().find(|fd| fd.symbol == symbol).unwrap(), window)
+    }
+    fn run(f: &StateFunction, inputs: &[f32]) -> Vec<f32> {
+        let mut mem = f.memory();
+        inputs.iter().map(|x| f.eval(*x, &mut mem)).collect()
+    }
+
+    #[test]
+    fn window_symbol_test() {
+        assert_eq!(ext("Sma", 10).symbol, "Sma10");
+        assert_eq!(ext("Sma", 0).window, 1);
+        assert_eq!(StateFunction::with_window(&SFREGISTRY[1], 5).symbol, "Ma2");
+        assert_eq!(StateFunction::with_window(&SFREGISTRY[1], 5).window, 1);
+    }
+
+    #[test]
+    fn lag_test() {
+        assert_eq!(run(&ext("Lag", 2), &[0.1, 0.2, 0.3, 0.4]), vec![0.0, 0.0, 0.1, 0.2]);
+    }
+
+    #[test]
+    fn sma_test() {
+        assert_eq!(run(&ext("Sma", 2), &[1.0, 1.0, -1.0]), run(&StateFunction::new(&SFREGISTRY[1]), &[1.0, 1.0, -1.0]));
+        assert_eq!(run(&ext("Sma", 4), &[1.0, 1.0, 1.0, 1.0, -1.0]), vec![0.25, 0.5, 0.75, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn ema_test() {
+        // alpha = 0.5
+        assert_eq!(run(&ext("Ema", 3), &[1.0, 1.0, -1.0]), vec![0.5, 0.75, -0.125]);
+    }
+
+    #[test]
+    fn rolling_min_max_sum_test() {
+        let x = [0.5, -0.5, 0.25, 0.75];
+        assert_eq!(run(&ext("RMin", 2), &x), vec![0.0, -0.5, -0.5, 0.25]);
+        assert_eq!(run(&ext("RMax", 3), &x), vec![0.5, 0.5, 0.5, 0.75]);
+        assert_eq!(run(&ext("RSum", 3), &x), vec![0.5, 0.0, 0.25, 0.5]);
+        assert_eq!(run(&ext("RSum", 2), &[0.75, 0.75]), vec![0.75, 1.0]);
+    }
+
+    #[test]
+    fn mom_test() {
+        assert_eq!(run(&ext("Mom", 2), &[0.5, 0.25, -0.75, -1.0]), vec![0.5, 0.25, -1.0, -1.0]);
+    }
+
+    #[test]
+    fn zscore_test() {
+        let z = run(&ext("ZSc", 4), &[0.5, 0.5, 0.5, 0.5, 0.5]);
+        assert_eq!(z[4], 0.0);
+        // window [1, -1]: mean 0, std 1
+        assert_eq!(run(&ext("ZSc", 2), &[-1.0, 1.0])[1], 0.5);
+        assert_eq!(run(&ext("ZSc", 2), &[1.0, -1.0])[1], -0.5);
+    }
 }
@@ -6,23 +6,40 @@ pub struct Statistics {
     win: f32,
     loss: f32,
     profit_factor: f32,
+    // bars where a position was held or changed
     count: u32,
     percent_profitable: f32,
     avg_win: f32,
-    avg_loss: f32
+    avg_loss: f32,
+    // average absolute position over all bars
+    exposure: f32,
+    // sum of absolute position changes
+    turnover: f32
 }
 
 impl Statistics{
-    pub fn new(equity: &Vec<f32>) -> Self{
+    // positions may be fractional; a bar counts when a position was held or changed, so the
+    // cost of going flat is included and result equals the last equity
+    pub fn new(equity: &[f32], positions: &[f32]) -> Self{
         let mut prev = 0.0;
         let mut win = 0.0;
         let mut loss = 0.0;
         let mut win_cnt = 0;
-        let cnt = equity.len() as u32;
+        let mut cnt = 0;
+        let mut exposure = 0.0;
+        let mut turnover = 0.0;
+        let mut prev_pos = 0.0;
         for i in 0..equity.len(){
             let temp = equity[i];
             let res = temp - prev;
             prev = temp;
+            let pos = positions[i];
+            exposure += pos.abs();
+            turnover += (pos - prev_pos).abs();
+            let traded = pos != 0.0 || pos != prev_pos;
+            prev_pos = pos;
+            if !traded { continue }
+            cnt += 1;
             if res > 0.0 {
                 win_cnt += 1;
                 win += res;
@@ -37,9 +54,61 @@ impl Statistics{
             loss,
             profit_factor: if loss == 0.0{1000000.0} else {win/loss},
             count: cnt,
-            percent_profitable: 100.0 * (win_cnt as f32)/(cnt as f32),
+            percent_profitable: if cnt == 0 {0.0} else {100.0 * (win_cnt as f32)/(cnt as f32)},
             avg_win: if win_cnt == 0 {0.0} else {win/(win_cnt as f32)},
-            avg_loss: if win_cnt == cnt {0.0} else {loss/((cnt - win_cnt) as f32)}
+            avg_loss: if win_cnt == cnt {0.0} else {loss/((cnt - win_cnt) as f32)},
+            exposure: if equity.is_empty() {0.0} else {exposure/(equity.len() as f32)},
+            turnover
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codons::Codon;
+    use crate::fitness_evaluator::{Costs, FitnessEvaluator, FitnessFunction};
+    use crate::linking_function::Average;
+    use crate::program::Program;
+    use crate::terminal::Terminal;
+
+    #[test]
+    fn fractional_positions_test() {
+        // returns 1, -2, 4, 1 with positions 0.5, -0.5, 0, 1; the flat bar closes a position
+        let positions = [0.5, -0.5, 0.0, 1.0];
+        let equity = [0.5, 1.5, 1.5, 2.5];
+        let s = Statistics::new(&equity, &positions);
+        assert_eq!(s.count, 4);
+        assert_eq!(s.win, 2.5);
+        assert_eq!(s.loss, 0.0);
+        assert_eq!(s.result, 2.5);
+        assert_eq!(s.percent_profitable, 75.0);
+        assert_eq!(s.exposure, 0.5);
+        assert_eq!(s.turnover, 3.0);
+    }
+
+    #[test]
+    fn flat_test() {
+        let s = Statistics::new(&[0.0, 0.0], &[0.0, 0.0]);
+        assert_eq!(s.count, 0);
+        assert_eq!(s.percent_profitable, 0.0);
+        assert_eq!(s.exposure, 0.0);
+    }
+
+    #[test]
+    fn flat_exit_cost_test() {
+        // the position follows args[0], every row returns 1 and a unit of turnover costs 0.25
+        let args: Vec<Vec<f32>> = [1.0, 1.0, 0.0, 0.0, 1.0, 0.0].iter().map(|a| vec![*a]).collect();
+        let mut ff = FitnessFunction::new(args, vec![1.0; 6], 1.0);
+        ff.set_costs(Costs::new(0.25, 0.0, 0.0));
+        let codons = vec![Codon::Terminal(Terminal::new(0))];
+        let equity = ff.equity(1, true, &mut Program::with_linker(&codons, 1, 1, &Average, &[]));
+        let positions = ff.outputs(1, true, &mut Program::with_linker(&codons, 1, 1, &Average, &[]));
+        let s = Statistics::new(&equity, &positions);
+        // both exits to flat cost 0.25, the bar that stays flat is not counted
+        assert_eq!(s.result, *equity.last().unwrap());
+        assert_eq!(s.result, 2.0);
+        assert_eq!(s.loss, 0.5);
+        assert_eq!(s.count, 5);
+    }
+}
@@ -0,0 +1,282 @@
+use crate::data_table::DataTable;
+use crate::delay_line::DelayLine;
+use crate::fitness_evaluator::FitnessEvaluator;
+use crate::program::Program;
+
+// evaluators for symbolic regression and classification; chromosome outputs are in [-1,1],
+// so regression targets should be scaled into the same range (and a continuous linking
+// function like Mean used)
+
+// (output, target) of every row of the train or test data
+fn predictions(data_table: &DataTable, end_of_train_data: usize, max_delay: usize, train: bool, program: &mut Program) -> Vec<(f32, f32)> {
+    let rows = if train {0..end_of_train_data} else {end_of_train_data..data_table.rows};
+    let mut ret = Vec::with_capacity(rows.len());
+    let mut dl = DelayLine::new(max_delay);
+    program.reset();
+    for i in rows {
+        let row = data_table.get_data_row(i).unwrap();
+        let s = program.eval(row.0, &dl);
+        dl.push(s);
+        ret.push((s, row.1));
+    }
+    ret
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RegressionMetric {
+    Mse,
+    Rmse,
+    R2,
+}
+
+impl RegressionMetric {
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "MSE" => Some(RegressionMetric::Mse),
+            "RMSE" => Some(RegressionMetric::Rmse),
+            "R2" => Some(RegressionMetric::R2),
+            _ => None,
+        }
+    }
+}
+
+// mean squared error and coefficient of determination
+fn mse_r2(p: &[(f32, f32)]) -> (f32, f32) {
+    if p.is_empty() { return (0.0, 0.0) }
+    let n = p.len() as f32;
+    let mean = p.iter().map(|(_, t)| t).sum::<f32>() / n;
+    let sse = p.iter().map(|(o, t)| (o - t) * (o - t)).sum::<f32>();
+    let sst = p.iter().map(|(_, t)| (t - mean) * (t - mean)).sum::<f32>();
+    let r2 = if sst > 0.0 {1.0 - sse / sst} else if sse == 0.0 {1.0} else {0.0};
+    (sse / n, r2)
+}
+
+pub struct RegressionEvaluator {
+    data_table: DataTable,
+    end_of_train_data: usize,
+    pub metric: RegressionMetric,
+}
+
+impl RegressionEvaluator {
+    pub fn new(a: Vec<Vec<f32>>, r: Vec<f32>, train_fraction: f32, metric: RegressionMetric) -> Self {
+        let end_of_train_data = ((r.len() as f32) * train_fraction) as usize;
+        RegressionEvaluator { data_table: DataTable::new(a, r).unwrap(), end_of_train_data, metric }
+    }
+
+    // fitness in [0,1]: 1/(1+MSE), 1/(1+RMSE) or R2 clipped at zero
+    pub fn score(&self, p: &[(f32, f32)]) -> f32 {
+        let (mse, r2) = mse_r2(p);
+        match self.metric {
+            RegressionMetric::Mse => 1.0 / (1.0 + mse),
+            RegressionMetric::Rmse => 1.0 / (1.0 + mse.sqrt()),
+            RegressionMetric::R2 => r2.max(0.0),
+        }
+    }
+
+    // all metrics on train or test data, for reporting
+    pub fn report(&self, max_delay: usize, train: bool, program: &mut Program) -> Vec<(&'static str, f32)> {
+        let p = predictions(&self.data_table, self.end_of_train_data, max_delay, train, program);
+        let (mse, r2) = mse_r2(&p);
+        vec![("mse", mse), ("rmse", mse.sqrt()), ("r2", r2), ("fitness", self.score(&p))]
+    }
+}
+
+impl FitnessEvaluator for RegressionEvaluator {
+    fn evaluate(&self, max_delay: usize, program: &mut Program) -> f32 {
+        self.score(&predictions(&self.data_table, self.end_of_train_data, max_delay, true, program))
+    }
+
+    // cumulative negative squared error
+    fn equity(&self, max_delay: usize, train: bool, program: &mut Program) -> Vec<f32> {
+        let mut res = 0.0f32;
+        predictions(&self.data_table, self.end_of_train_data, max_delay, train, program).iter()
+            .map(|(o, t)| { res -= (o - t) * (o - t); res }).collect()
+    }
+
+    fn outputs(&self, max_delay: usize, train: bool, program: &mut Program) -> Vec<f32> {
+        predictions(&self.data_table, self.end_of_train_data, max_delay, train, program).iter().map(|(o, _)| *o).collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClassificationMetric {
+    Accuracy,
+    // F1 of class 1 for two classes, macro F1 otherwise
+    F1,
+    // fraction of rows with output within the hit threshold of the value of the target class
+    Hit,
+}
+
+impl ClassificationMetric {
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "Acc" => Some(ClassificationMetric::Accuracy),
+            "F1" => Some(ClassificationMetric::F1),
+            "Hit" => Some(ClassificationMetric::Hit),
+            _ => None,
+        }
+    }
+}
+
+// targets are class indexes 0..classes; class c is represented by the output value
+// -1 + 2c/(classes-1) (-1 and 1 for two classes) and an output predicts the nearest class
+pub struct ClassificationEvaluator {
+    data_table: DataTable,
+    end_of_train_data: usize,
+    classes: usize,
+    hit_threshold: f32,
+    pub metric: ClassificationMetric,
+}
+
+impl ClassificationEvaluator {
+    // less than two classes are treated as two, a negative hit threshold as zero
+    pub fn new(a: Vec<Vec<f32>>, r: Vec<f32>, train_fraction: f32, classes: usize, hit_threshold: f32, metric: ClassificationMetric) -> Self {
+        let end_of_train_data = ((r.len() as f32) * train_fraction) as usize;
+        ClassificationEvaluator {
+            data_table: DataTable::new(a, r).unwrap(),
+            end_of_train_data,
+            classes: classes.max(2),
+            hit_threshold: hit_threshold.max(0.0),
+            metric,
+        }
+    }
+
+    pub fn class_value(&self, class: usize) -> f32 {
+        -1.0 + 2.0 * class as f32 / (self.classes - 1) as f32
+    }
+
+    pub fn predict(&self, output: f32) -> usize {
+        let c = ((output.clamp(-1.0, 1.0) + 1.0) * (self.classes - 1) as f32 / 2.0).round();
+        c as usize
+    }
+
+    fn target(&self, t: f32) -> usize {
+        (t.max(0.0).round() as usize).min(self.classes - 1)
+    }
+
+    fn accuracy(&self, p: &[(f32, f32)]) -> f32 {
+        if p.is_empty() { return 0.0 }
+        p.iter().filter(|(o, t)| self.predict(*o) == self.target(*t)).count() as f32 / p.len() as f32
+    }
+
+    fn f1(&self, p: &[(f32, f32)]) -> f32 {
+        let f1_of = |c: usize| {
+            let tp = p.iter().filter(|(o, t)| self.predict(*o) == c && self.target(*t) == c).count() as f32;
+            let predicted = p.iter().filter(|(o, _)| self.predict(*o) == c).count() as f32;
+            let actual = p.iter().filter(|(_, t)| self.target(*t) == c).count() as f32;
+            if predicted + actual == 0.0 {None} else {Some(2.0 * tp / (predicted + actual))}
+        };
+        if self.classes == 2 { return f1_of(1).unwrap_or(0.0) }
+        let f1s: Vec<f32> = (0..self.classes).filter_map(f1_of).collect();
+        if f1s.is_empty() {0.0} else {f1s.iter().sum::<f32>() / f1s.len() as f32}
+    }
+
+    fn hits(&self, p: &[(f32, f32)]) -> f32 {
+        if p.is_empty() { return 0.0 }
+        p.iter().filter(|(o, t)| (o - self.class_value(self.target(*t))).abs() <= self.hit_threshold).count() as f32 / p.len() as f32
+    }
+
+    // fitness in [0,1]
+    pub fn score(&self, p: &[(f32, f32)]) -> f32 {
+        match self.metric {
+            ClassificationMetric::Accuracy => self.accuracy(p),
+            ClassificationMetric::F1 => self.f1(p),
+            ClassificationMetric::Hit => self.hits(p),
+        }
+    }
+
+    // all metrics on train or test data, for reporting
+    pub fn report(&self, max_delay: usize, train: bool, program: &mut Program) -> Vec<(&'static str, f32)> {
+        let p = predictions(&self.data_table, self.end_of_train_data, max_delay, train, program);
+        vec![("accuracy", self.accuracy(&p)), ("f1", self.f1(&p)), ("hits", self.hits(&p)), ("fitness", self.score(&p))]
+    }
+}
+
+impl FitnessEvaluator for ClassificationEvaluator {
+    fn evaluate(&self, max_delay: usize, program: &mut Program) -> f32 {
+        self.score(&predictions(&self.data_table, self.end_of_train_data, max_delay, true, program))
+    }
+
+    // cumulative number of correctly classified rows
+    fn equity(&self, max_delay: usize, train: bool, program: &mut Program) -> Vec<f32> {
+        let mut res = 0.0f32;
+        predictions(&self.data_table, self.end_of_train_data, max_delay, train, program).iter()
+            .map(|(o, t)| { if self.predict(*o) == self.target(*t) { res += 1.0 } res }).collect()
+    }
+
+    fn outputs(&self, max_delay: usize, train: bool, program: &mut Program) -> Vec<f32> {
+        predictions(&self.data_table, self.end_of_train_data, max_delay, train, program).iter().map(|(o, _)| *o).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::codons::Codon;
+    use crate::functions::{Function, Primitive, FREGISTRY};
+    use crate::linking_function::Average;
+    use crate::terminal::Terminal;
+
+    fn inputs() -> Vec<Vec<f32>> {
+        (0..10).map(|i| vec![i as f32 / 10.0 - 0.5, 0.5 - i as f32 / 20.0]).collect()
+    }
+
+    #[test]
+    fn regression_test() {
+        // target is Avg(args[0], args[1])
+        let targets: Vec<f32> = inputs().iter().map(|a| (a[0] + a[1]) / 2.0).collect();
+        let codons = vec![
+            Codon::Function(Function::new(Primitive::builtin(&FREGISTRY[2]))),
+            Codon::Terminal(Terminal::new(0)),
+            Codon::Terminal(Terminal::new(1)),
+        ];
+        let lf = Arc::new(Average);
+        let mut p = Program::with_linker(&codons, 3, 1, lf.as_ref(), &[]);
+        for m in [RegressionMetric::Mse, RegressionMetric::Rmse, RegressionMetric::R2] {
+            let re = RegressionEvaluator::new(inputs(), targets.clone(), 0.5, m);
+            assert!((re.evaluate(1, &mut p) - 1.0).abs() < 1e-6);
+        }
+        // args[0] alone: mse of (a0 - a1) / 2
+        let mut p = Program::with_linker(&codons[1..], 1, 1, lf.as_ref(), &[]);
+        let re = RegressionEvaluator::new(inputs(), targets, 0.5, RegressionMetric::Mse);
+        let mse = inputs()[..5].iter().map(|a| (a[0] - a[1]) * (a[0] - a[1]) / 4.0).sum::<f32>() / 5.0;
+        assert!((re.evaluate(1, &mut p) - 1.0 / (1.0 + mse)).abs() < 1e-6);
+        let report = re.report(1, true, &mut p);
+        assert!((report[0].1 - mse).abs() < 1e-6);
+        assert!(report[2].1 < 0.0);
+        assert_eq!(re.outputs(1, false, &mut p), inputs()[5..].iter().map(|a| a[0]).collect::<Vec<f32>>());
+    }
+
+    #[test]
+    fn binary_classification_test() {
+        // class 1 if args[0] > 0, args[0] is -0.5 .. 0.4
+        let targets = inputs().iter().map(|a| if a[0] > 0.0 {1.0} else {0.0}).collect();
+        let codons = vec![Codon::Terminal(Terminal::new(0))];
+        let mut p = Program::new(&codons, 1, 1);
+        let ce = ClassificationEvaluator::new(inputs(), targets, 1.0, 2, 0.1, ClassificationMetric::Accuracy);
+        // Maj outputs sign, zero (row 5) counts as long but its target is 0
+        assert_eq!(ce.evaluate(1, &mut p), 0.9);
+        let report = ce.report(1, true, &mut p);
+        // tp 4, predicted 5, actual 4
+        assert!((report[1].1 - 8.0 / 9.0).abs() < 1e-6);
+        assert_eq!(report[2].1, 0.9);
+        assert_eq!(ce.equity(1, true, &mut p)[9], 9.0);
+    }
+
+    #[test]
+    fn multiclass_test() {
+        let ce = ClassificationEvaluator::new(inputs(), vec![0.0; 10], 1.0, 3, 0.2, ClassificationMetric::F1);
+        assert_eq!(ce.class_value(1), 0.0);
+        assert_eq!(ce.predict(-0.6), 0);
+        assert_eq!(ce.predict(0.4), 1);
+        assert_eq!(ce.predict(0.6), 2);
+        let p = [(-0.9, 0.0), (0.1, 1.0), (0.9, 2.0), (0.5, 2.0)];
+        assert_eq!(ce.accuracy(&p), 1.0);
+        assert_eq!(ce.f1(&p), 1.0);
+        assert_eq!(ce.hits(&p), 0.75);
+        let p = [(-0.9, 0.0), (0.1, 0.0), (0.9, 2.0)];
+        // F1 of classes 0, 1 and 2: 2/3, 0, 1
+        assert!((ce.f1(&p) - 5.0 / 9.0).abs() < 1e-6);
+    }
+}
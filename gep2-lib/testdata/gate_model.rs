@@ -0,0 +1,221 @@
+// adf0 = RMax5(Max(Diff(args[0]), RMin2(args[0])))
+// adf1 = Abs(Mul(args[0], ZSc2(args[0])))
+// adf2 = Lag5(Or(Min(args[2], -0.3954388), Add(z[1], args[0])))
+// Gate~4(Ema3(RMax2(Mom3(adf0))), RSum2(Sub(Med3(adf0, adf2, adf2), adf1)))
+pub struct GateModel {
+    memory: Vec<Memory>,
+    // previous outputs
+    delay: DelayLine,
+}
+
+impl GateModel {
+    pub fn new() -> Self {
+        GateModel { memory: vec![Memory::new(1), Memory::new(2), Memory::new(5), Memory::new(2), Memory::new(5), Memory::new(3), Memory::new(2), Memory::new(3), Memory::new(2)], delay: DelayLine::new(2) }
+    }
+
+    // output for a row of inputs
+    pub fn step(&mut self, args: &[f32]) -> f32 {
+        let z: [f32; 2] = std::array::from_fn(|i| self.delay.get_shifted_back(i));
+        let s = model(args, &z, &mut self.memory);
+        self.delay.push(s);
+        s
+    }
+}
+
+impl Default for GateModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// memory holds 9 memories of state functions
+#[allow(unused_variables)]
+fn model(args: &[f32], z: &[f32], memory: &mut [Memory]) -> f32 {
+    let args: [f32; 3] = std::array::from_fn(|i| args[i].clamp(-1.0, 1.0));
+    let z: [f32; 2] = std::array::from_fn(|i| z[i].clamp(-1.0, 1.0));
+    let adf0 = f32::clamp(rmax5(max(diff(args[0], &mut memory[0]), rmin2(args[0], &mut memory[1])), &mut memory[2]), -1.0, 1.0);
+    let adf1 = f32::clamp(abs(mul(args[0], zsc2(args[0], &mut memory[3]))), -1.0, 1.0);
+    let adf2 = f32::clamp(lag5(or(min(args[2], -0.3954388), add(z[1], args[0])), &mut memory[4]), -1.0, 1.0);
+    let h0 = ema3(rmax2(mom3(adf0, &mut memory[5]), &mut memory[6]), &mut memory[7]);
+    let h1 = rsum2(sub(med3(adf0, adf2, adf2), adf1), &mut memory[8]);
+    gate_c4(&[h0, h1])
+}
+
+fn min(a0: f32, a1: f32) -> f32 {
+    a0.min(a1)
+}
+
+fn max(a0: f32, a1: f32) -> f32 {
+    a0.max(a1)
+}
+
+fn mul(a0: f32, a1: f32) -> f32 {
+    a0*a1
+}
+
+fn add(a0: f32, a1: f32) -> f32 {
+    clip(a0+a1)
+}
+
+fn sub(a0: f32, a1: f32) -> f32 {
+    clip(a0-a1)
+}
+
+fn abs(a0: f32) -> f32 {
+    a0.abs()
+}
+
+fn or(a0: f32, a1: f32) -> f32 {
+    let (x, y) = (to_truth(a0), to_truth(a1));
+    from_truth(x + y - x*y)
+}
+
+fn med3(a0: f32, a1: f32, a2: f32) -> f32 {
+    a0.max(a1).min(a0.min(a1).max(a2))
+}
+
+fn rmax5(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, rmax_op)
+}
+
+fn diff(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, diff_op)
+}
+
+fn rmin2(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, rmin_op)
+}
+
+fn zsc2(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, zsc_op)
+}
+
+fn lag5(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, lag_op)
+}
+
+fn ema3(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, ema_op)
+}
+
+fn rmax2(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, rmax_op)
+}
+
+fn mom3(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, mom_op)
+}
+
+fn rsum2(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, rsum_op)
+}
+
+fn diff_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    clip(curr - prev.get_shifted_back(0))
+}
+
+fn lag_op(_curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    prev.get_shifted_back(prev.capacity - 1)
+}
+
+fn ema_op(curr: f32, prev: &DelayLine, out: f32) -> f32 {
+    out + 2.0 / (prev.capacity as f32 + 1.0) * (curr - out)
+}
+
+fn rmin_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    rolling(curr, prev).fold(curr, f32::min)
+}
+
+fn rmax_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    rolling(curr, prev).fold(curr, f32::max)
+}
+
+fn rsum_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    clip(rolling(curr, prev).sum::<f32>())
+}
+
+fn mom_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    clip(curr - prev.get_shifted_back(prev.capacity - 1))
+}
+
+fn zsc_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    let n = prev.capacity as f32;
+    let mean = rolling(curr, prev).sum::<f32>() / n;
+    let var = rolling(curr, prev).map(|x| (x - mean) * (x - mean)).sum::<f32>() / n;
+    if var <= 0.0 {0.0} else {clip(0.5 * (curr - mean) / var.sqrt())}
+}
+
+fn gate_level(o: &[f32]) -> f32 {
+    if o.len() < 2 { return o[0] }
+    if o[0] > 0.0 { mean_of(&o[1..]) } else {0.0}
+}
+
+fn gate_c4(o: &[f32]) -> f32 {
+    let x = gate_level(o).clamp(-1.0, 1.0);
+    (x * 4.0).round() / 4.0
+}
+
+fn clip(x: f32) -> f32 {
+    x.clamp(-1.0, 1.0)
+}
+
+fn to_truth(x: f32) -> f32 {
+    0.5*(x + 1.0)
+}
+
+fn from_truth(t: f32) -> f32 {
+    2.0*t - 1.0
+}
+
+fn mean_of(x: &[f32]) -> f32 {
+    x.iter().sum::<f32>() / x.len() as f32
+}
+
+fn rolling(curr: f32, prev: &DelayLine) -> impl Iterator<Item = f32> + '_ {
+    std::iter::once(curr).chain((0..prev.capacity - 1).map(move |k| prev.get_shifted_back(k)))
+}
+
+// memory of a state function: previous inputs and previous output
+#[allow(dead_code)]
+struct Memory {
+    inputs: DelayLine,
+    output: f32,
+}
+
+#[allow(dead_code)]
+impl Memory {
+    fn new(window: usize) -> Self {
+        Memory { inputs: DelayLine::new(window), output: 0.0 }
+    }
+
+    fn step(&mut self, curr: f32, op: fn(f32, &DelayLine, f32) -> f32) -> f32 {
+        let ret = op(curr, &self.inputs, self.output);
+        self.inputs.push(curr);
+        self.output = ret;
+        ret
+    }
+}
+
+struct DelayLine {
+    capacity: usize,
+    idx: usize,
+    buff: Vec<f32>,
+}
+
+impl DelayLine {
+    fn new(capacity: usize) -> Self {
+        let c = if capacity < 1 {1} else {capacity};
+        DelayLine { capacity: c, idx: 0, buff: vec![0.0; c] }
+    }
+
+    fn push(&mut self, v: f32) {
+        self.buff[self.idx] = v;
+        self.idx += 1;
+        if self.idx == self.capacity {self.idx = 0}
+    }
+
+    fn get_shifted_back(&self, steps: usize) -> f32 {
+        let i = (steps + 1) % self.capacity;
+        self.buff[if i > self.idx {self.idx + self.capacity - i} else {self.idx - i}]
+    }
+}
@@ -0,0 +1,118 @@
+// Maj(Avg(Neg(0.42178488), Avg(z[0], args[0])), Avg(Neg(Sub(args[1], -0.9757658)), Sub(args[0], -0.9757658)), Neg(Ma2(Gt(If(args[1], -0.007266283, z[0]), args[2]))))
+pub struct MajModel {
+    memory: Vec<Memory>,
+    // previous outputs
+    delay: DelayLine,
+}
+
+impl MajModel {
+    pub fn new() -> Self {
+        MajModel { memory: vec![Memory::new(1)], delay: DelayLine::new(2) }
+    }
+
+    // output for a row of inputs
+    pub fn step(&mut self, args: &[f32]) -> f32 {
+        let z: [f32; 1] = std::array::from_fn(|i| self.delay.get_shifted_back(i));
+        let s = model(args, &z, &mut self.memory);
+        self.delay.push(s);
+        s
+    }
+}
+
+impl Default for MajModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// memory holds 1 memories of state functions
+#[allow(unused_variables)]
+fn model(args: &[f32], z: &[f32], memory: &mut [Memory]) -> f32 {
+    let args: [f32; 3] = std::array::from_fn(|i| args[i].clamp(-1.0, 1.0));
+    let z: [f32; 1] = std::array::from_fn(|i| z[i].clamp(-1.0, 1.0));
+    let g0 = avg(neg(0.42178488), avg(z[0], args[0]));
+    let g1 = avg(neg(sub(args[1], -0.9757658)), sub(args[0], -0.9757658));
+    let g2 = neg(ma2(gt(if_(args[1], -0.007266283, z[0]), args[2]), &mut memory[0]));
+    maj(&[g0, g1, g2])
+}
+
+fn avg(a0: f32, a1: f32) -> f32 {
+    (a0+a1)/2.0
+}
+
+fn neg(a0: f32) -> f32 {
+    -a0
+}
+
+fn sub(a0: f32, a1: f32) -> f32 {
+    clip(a0-a1)
+}
+
+fn gt(a0: f32, a1: f32) -> f32 {
+    if a0 > a1 {1.0} else {-1.0}
+}
+
+fn if_(a0: f32, a1: f32, a2: f32) -> f32 {
+    if a0 > 0.0 {a1} else {a2}
+}
+
+fn ma2(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, ma2_op)
+}
+
+fn ma2_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    0.5*(curr + prev.get_shifted_back(0))
+}
+
+fn maj(o: &[f32]) -> f32 {
+    o.iter().sum::<f32>().signum()
+}
+
+fn clip(x: f32) -> f32 {
+    x.clamp(-1.0, 1.0)
+}
+
+// memory of a state function: previous inputs and previous output
+#[allow(dead_code)]
+struct Memory {
+    inputs: DelayLine,
+    output: f32,
+}
+
+#[allow(dead_code)]
+impl Memory {
+    fn new(window: usize) -> Self {
+        Memory { inputs: DelayLine::new(window), output: 0.0 }
+    }
+
+    fn step(&mut self, curr: f32, op: fn(f32, &DelayLine, f32) -> f32) -> f32 {
+        let ret = op(curr, &self.inputs, self.output);
+        self.inputs.push(curr);
+        self.output = ret;
+        ret
+    }
+}
+
+struct DelayLine {
+    capacity: usize,
+    idx: usize,
+    buff: Vec<f32>,
+}
+
+impl DelayLine {
+    fn new(capacity: usize) -> Self {
+        let c = if capacity < 1 {1} else {capacity};
+        DelayLine { capacity: c, idx: 0, buff: vec![0.0; c] }
+    }
+
+    fn push(&mut self, v: f32) {
+        self.buff[self.idx] = v;
+        self.idx += 1;
+        if self.idx == self.capacity {self.idx = 0}
+    }
+
+    fn get_shifted_back(&self, steps: usize) -> f32 {
+        let i = (steps + 1) % self.capacity;
+        self.buff[if i > self.idx {self.idx + self.capacity - i} else {self.idx - i}]
+    }
+}
@@ -0,0 +1,166 @@
+// Mean(Neg(And(Mul(z[0], args[0]), -0.8975086)), Min(Ma2(Diff(args[1])), Thr(args[0], -1.1151574)), Sub(Ema20(RMax20(z[0])), Sub(args[0], args[0])))
+pub struct MeanModel {
+    memory: Vec<Memory>,
+    // previous outputs
+    delay: DelayLine,
+}
+
+impl MeanModel {
+    pub fn new() -> Self {
+        MeanModel { memory: vec![Memory::new(1), Memory::new(1), Memory::new(20), Memory::new(20)], delay: DelayLine::new(2) }
+    }
+
+    // output for a row of inputs
+    pub fn step(&mut self, args: &[f32]) -> f32 {
+        let z: [f32; 1] = std::array::from_fn(|i| self.delay.get_shifted_back(i));
+        let s = model(args, &z, &mut self.memory);
+        self.delay.push(s);
+        s
+    }
+}
+
+impl Default for MeanModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// memory holds 4 memories of state functions
+#[allow(unused_variables)]
+fn model(args: &[f32], z: &[f32], memory: &mut [Memory]) -> f32 {
+    let args: [f32; 2] = std::array::from_fn(|i| args[i].clamp(-1.0, 1.0));
+    let z: [f32; 1] = std::array::from_fn(|i| z[i].clamp(-1.0, 1.0));
+    let g0 = neg(and(mul(z[0], args[0]), -0.8975086));
+    let g1 = min(ma2(diff(args[1], &mut memory[0]), &mut memory[1]), thr(args[0], -1.1151574));
+    let g2 = sub(ema20(rmax20(z[0], &mut memory[2]), &mut memory[3]), sub(args[0], args[0]));
+    mean(&[g0, g1, g2])
+}
+
+fn min(a0: f32, a1: f32) -> f32 {
+    a0.min(a1)
+}
+
+fn neg(a0: f32) -> f32 {
+    -a0
+}
+
+fn thr(a0: f32, a1: f32) -> f32 {
+    if a0.abs() > a1.abs() {sign(a0)} else {0.0}
+}
+
+fn mul(a0: f32, a1: f32) -> f32 {
+    a0*a1
+}
+
+fn sub(a0: f32, a1: f32) -> f32 {
+    clip(a0-a1)
+}
+
+fn and(a0: f32, a1: f32) -> f32 {
+    from_truth(to_truth(a0)*to_truth(a1))
+}
+
+fn ma2(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, ma2_op)
+}
+
+fn diff(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, diff_op)
+}
+
+fn ema20(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, ema_op)
+}
+
+fn rmax20(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, rmax_op)
+}
+
+fn ma2_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    0.5*(curr + prev.get_shifted_back(0))
+}
+
+fn diff_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    clip(curr - prev.get_shifted_back(0))
+}
+
+fn ema_op(curr: f32, prev: &DelayLine, out: f32) -> f32 {
+    out + 2.0 / (prev.capacity as f32 + 1.0) * (curr - out)
+}
+
+fn rmax_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    rolling(curr, prev).fold(curr, f32::max)
+}
+
+fn mean(o: &[f32]) -> f32 {
+    mean_of(o)
+}
+
+fn clip(x: f32) -> f32 {
+    x.clamp(-1.0, 1.0)
+}
+
+fn sign(x: f32) -> f32 {
+    if x > 0.0 {1.0} else if x < 0.0 {-1.0} else {0.0}
+}
+
+fn to_truth(x: f32) -> f32 {
+    0.5*(x + 1.0)
+}
+
+fn from_truth(t: f32) -> f32 {
+    2.0*t - 1.0
+}
+
+fn mean_of(x: &[f32]) -> f32 {
+    x.iter().sum::<f32>() / x.len() as f32
+}
+
+fn rolling(curr: f32, prev: &DelayLine) -> impl Iterator<Item = f32> + '_ {
+    std::iter::once(curr).chain((0..prev.capacity - 1).map(move |k| prev.get_shifted_back(k)))
+}
+
+// memory of a state function: previous inputs and previous output
+#[allow(dead_code)]
+struct Memory {
+    inputs: DelayLine,
+    output: f32,
+}
+
+#[allow(dead_code)]
+impl Memory {
+    fn new(window: usize) -> Self {
+        Memory { inputs: DelayLine::new(window), output: 0.0 }
+    }
+
+    fn step(&mut self, curr: f32, op: fn(f32, &DelayLine, f32) -> f32) -> f32 {
+        let ret = op(curr, &self.inputs, self.output);
+        self.inputs.push(curr);
+        self.output = ret;
+        ret
+    }
+}
+
+struct DelayLine {
+    capacity: usize,
+    idx: usize,
+    buff: Vec<f32>,
+}
+
+impl DelayLine {
+    fn new(capacity: usize) -> Self {
+        let c = if capacity < 1 {1} else {capacity};
+        DelayLine { capacity: c, idx: 0, buff: vec![0.0; c] }
+    }
+
+    fn push(&mut self, v: f32) {
+        self.buff[self.idx] = v;
+        self.idx += 1;
+        if self.idx == self.capacity {self.idx = 0}
+    }
+
+    fn get_shifted_back(&self, steps: usize) -> f32 {
+        let i = (steps + 1) % self.capacity;
+        self.buff[if i > self.idx {self.idx + self.capacity - i} else {self.idx - i}]
+    }
+}
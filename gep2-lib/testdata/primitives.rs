@@ -0,0 +1,280 @@
+// built-in function of a symbol applied to arguments a
+pub fn function(symbol: &str, a: &[f32]) -> f32 {
+    match symbol {
+        "Min" => min(a[0], a[1]),
+        "Max" => max(a[0], a[1]),
+        "Avg" => avg(a[0], a[1]),
+        "Neg" => neg(a[0]),
+        "Sgn" => sgn(a[0]),
+        "Thr" => thr(a[0], a[1]),
+        "Mul" => mul(a[0], a[1]),
+        "Add" => add(a[0], a[1]),
+        "Sub" => sub(a[0], a[1]),
+        "Abs" => abs(a[0]),
+        "And" => and(a[0], a[1]),
+        "Or" => or(a[0], a[1]),
+        "Gt" => gt(a[0], a[1]),
+        "Lt" => lt(a[0], a[1]),
+        "If" => if_(a[0], a[1], a[2]),
+        "Med3" => med3(a[0], a[1], a[2]),
+        _ => unreachable!(),
+    }
+}
+
+// outputs of a built-in state function of window length `window` for a series of inputs
+pub fn state_function(symbol: &str, window: usize, inputs: &[f32]) -> Vec<f32> {
+    let op: fn(f32, &DelayLine, f32) -> f32 = match symbol {
+        "Delay" => delay_op,
+        "Ma2" => ma2_op,
+        "Diff" => diff_op,
+        "Lag" => lag_op,
+        "Sma" => sma_op,
+        "Ema" => ema_op,
+        "RMin" => rmin_op,
+        "RMax" => rmax_op,
+        "RSum" => rsum_op,
+        "Mom" => mom_op,
+        "ZSc" => zsc_op,
+        _ => unreachable!(),
+    };
+    let mut m = Memory::new(window);
+    inputs.iter().map(|x| m.step(*x, op)).collect()
+}
+
+// evaluate and level of a built-in linking function with outputs o and weights w
+pub fn linker(symbol: &str, o: &[f32], w: &[f32]) -> (f32, f32) {
+    match symbol {
+        "Maj" => (maj(o), maj_level(o)),
+        "WSum" => (wsum(o, w), wsum_level(o, w)),
+        "Mean" => (mean(o), mean_level(o)),
+        "Unan" => (unan(o), unan_level(o)),
+        "Gate" => (gate(o), gate_level(o)),
+        _ => unreachable!(),
+    }
+}
+
+fn min(a0: f32, a1: f32) -> f32 {
+    a0.min(a1)
+}
+
+fn max(a0: f32, a1: f32) -> f32 {
+    a0.max(a1)
+}
+
+fn avg(a0: f32, a1: f32) -> f32 {
+    (a0+a1)/2.0
+}
+
+fn neg(a0: f32) -> f32 {
+    -a0
+}
+
+fn sgn(a0: f32) -> f32 {
+    sign(a0)
+}
+
+fn thr(a0: f32, a1: f32) -> f32 {
+    if a0.abs() > a1.abs() {sign(a0)} else {0.0}
+}
+
+fn mul(a0: f32, a1: f32) -> f32 {
+    a0*a1
+}
+
+fn add(a0: f32, a1: f32) -> f32 {
+    clip(a0+a1)
+}
+
+fn sub(a0: f32, a1: f32) -> f32 {
+    clip(a0-a1)
+}
+
+fn abs(a0: f32) -> f32 {
+    a0.abs()
+}
+
+fn and(a0: f32, a1: f32) -> f32 {
+    from_truth(to_truth(a0)*to_truth(a1))
+}
+
+fn or(a0: f32, a1: f32) -> f32 {
+    let (x, y) = (to_truth(a0), to_truth(a1));
+    from_truth(x + y - x*y)
+}
+
+fn gt(a0: f32, a1: f32) -> f32 {
+    if a0 > a1 {1.0} else {-1.0}
+}
+
+fn lt(a0: f32, a1: f32) -> f32 {
+    if a0 < a1 {1.0} else {-1.0}
+}
+
+fn if_(a0: f32, a1: f32, a2: f32) -> f32 {
+    if a0 > 0.0 {a1} else {a2}
+}
+
+fn med3(a0: f32, a1: f32, a2: f32) -> f32 {
+    a0.max(a1).min(a0.min(a1).max(a2))
+}
+
+fn delay_op(_curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    prev.get_shifted_back(0)
+}
+
+fn ma2_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    0.5*(curr + prev.get_shifted_back(0))
+}
+
+fn diff_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    clip(curr - prev.get_shifted_back(0))
+}
+
+fn lag_op(_curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    prev.get_shifted_back(prev.capacity - 1)
+}
+
+fn sma_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    rolling(curr, prev).sum::<f32>() / prev.capacity as f32
+}
+
+fn ema_op(curr: f32, prev: &DelayLine, out: f32) -> f32 {
+    out + 2.0 / (prev.capacity as f32 + 1.0) * (curr - out)
+}
+
+fn rmin_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    rolling(curr, prev).fold(curr, f32::min)
+}
+
+fn rmax_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    rolling(curr, prev).fold(curr, f32::max)
+}
+
+fn rsum_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    clip(rolling(curr, prev).sum::<f32>())
+}
+
+fn mom_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    clip(curr - prev.get_shifted_back(prev.capacity - 1))
+}
+
+fn zsc_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    let n = prev.capacity as f32;
+    let mean = rolling(curr, prev).sum::<f32>() / n;
+    let var = rolling(curr, prev).map(|x| (x - mean) * (x - mean)).sum::<f32>() / n;
+    if var <= 0.0 {0.0} else {clip(0.5 * (curr - mean) / var.sqrt())}
+}
+
+fn maj(o: &[f32]) -> f32 {
+    o.iter().sum::<f32>().signum()
+}
+
+fn maj_level(o: &[f32]) -> f32 {
+    mean_of(o)
+}
+
+fn wsum(o: &[f32], w: &[f32]) -> f32 {
+    sign(o.iter().zip(w).map(|(o, w)| o * w).sum::<f32>())
+}
+
+fn wsum_level(o: &[f32], w: &[f32]) -> f32 {
+    let norm = w.iter().map(|w| w.abs()).sum::<f32>();
+    if norm > 0.0 {o.iter().zip(w).map(|(o, w)| o * w).sum::<f32>() / norm} else {0.0}
+}
+
+fn mean(o: &[f32]) -> f32 {
+    mean_of(o)
+}
+
+fn mean_level(o: &[f32]) -> f32 {
+    mean_of(o)
+}
+
+fn unan(o: &[f32]) -> f32 {
+    if o.iter().all(|x| *x > 0.0) {1.0}
+    else if o.iter().all(|x| *x < 0.0) {-1.0}
+    else {0.0}
+}
+
+fn unan_level(o: &[f32]) -> f32 {
+    if o.iter().all(|x| *x > 0.0) || o.iter().all(|x| *x < 0.0) {mean_of(o)} else {0.0}
+}
+
+fn gate(o: &[f32]) -> f32 {
+    if o.len() < 2 { return sign(o[0]) }
+    if o[0] > 0.0 { sign(o[1..].iter().sum::<f32>()) } else {0.0}
+}
+
+fn gate_level(o: &[f32]) -> f32 {
+    if o.len() < 2 { return o[0] }
+    if o[0] > 0.0 { mean_of(&o[1..]) } else {0.0}
+}
+
+fn clip(x: f32) -> f32 {
+    x.clamp(-1.0, 1.0)
+}
+
+fn sign(x: f32) -> f32 {
+    if x > 0.0 {1.0} else if x < 0.0 {-1.0} else {0.0}
+}
+
+fn to_truth(x: f32) -> f32 {
+    0.5*(x + 1.0)
+}
+
+fn from_truth(t: f32) -> f32 {
+    2.0*t - 1.0
+}
+
+fn mean_of(x: &[f32]) -> f32 {
+    x.iter().sum::<f32>() / x.len() as f32
+}
+
+fn rolling(curr: f32, prev: &DelayLine) -> impl Iterator<Item = f32> + '_ {
+    std::iter::once(curr).chain((0..prev.capacity - 1).map(move |k| prev.get_shifted_back(k)))
+}
+
+// memory of a state function: previous inputs and previous output
+#[allow(dead_code)]
+struct Memory {
+    inputs: DelayLine,
+    output: f32,
+}
+
+#[allow(dead_code)]
+impl Memory {
+    fn new(window: usize) -> Self {
+        Memory { inputs: DelayLine::new(window), output: 0.0 }
+    }
+
+    fn step(&mut self, curr: f32, op: fn(f32, &DelayLine, f32) -> f32) -> f32 {
+        let ret = op(curr, &self.inputs, self.output);
+        self.inputs.push(curr);
+        self.output = ret;
+        ret
+    }
+}
+
+struct DelayLine {
+    capacity: usize,
+    idx: usize,
+    buff: Vec<f32>,
+}
+
+impl DelayLine {
+    fn new(capacity: usize) -> Self {
+        let c = if capacity < 1 {1} else {capacity};
+        DelayLine { capacity: c, idx: 0, buff: vec![0.0; c] }
+    }
+
+    fn push(&mut self, v: f32) {
+        self.buff[self.idx] = v;
+        self.idx += 1;
+        if self.idx == self.capacity {self.idx = 0}
+    }
+
+    fn get_shifted_back(&self, steps: usize) -> f32 {
+        let i = (steps + 1) % self.capacity;
+        self.buff[if i > self.idx {self.idx + self.capacity - i} else {self.idx - i}]
+    }
+}
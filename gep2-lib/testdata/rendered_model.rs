@@ -0,0 +1,11 @@
+// memory holds 4 memories of state functions
+#[allow(unused_variables)]
+fn model(args: &[f32], z: &[f32], memory: &mut [Memory]) -> f32 {
+    let args: [f32; 3] = std::array::from_fn(|i| args[i].clamp(-1.0, 1.0));
+    let z: [f32; 2] = std::array::from_fn(|i| z[i].clamp(-1.0, 1.0));
+    let adf0 = f32::clamp(sma3(diff(add(args[0], z[1]), &mut memory[0]), &mut memory[1]), -1.0, 1.0);
+    let adf1 = f32::clamp(mul(1.375, ma2(args[2], &mut memory[2])), -1.0, 1.0);
+    let h0 = diff(avg(adf0, adf1), &mut memory[3]);
+    let h1 = max(adf0, adf1);
+    mean_c4(&[h0, h1])
+}
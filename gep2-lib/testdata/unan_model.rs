@@ -0,0 +1,171 @@
+// Unan~3(Lag5(Delay(And(RMin20(-0.19805646), args[1]))), And(Sma20(Max(0.6101861, args[2])), Diff(z[0])), Sma2(Lt(z[1], Gt(args[0], args[0]))))
+pub struct UnanModel {
+    memory: Vec<Memory>,
+    // previous outputs
+    delay: DelayLine,
+}
+
+impl UnanModel {
+    pub fn new() -> Self {
+        UnanModel { memory: vec![Memory::new(20), Memory::new(1), Memory::new(5), Memory::new(20), Memory::new(1), Memory::new(2)], delay: DelayLine::new(2) }
+    }
+
+    // output for a row of inputs
+    pub fn step(&mut self, args: &[f32]) -> f32 {
+        let z: [f32; 2] = std::array::from_fn(|i| self.delay.get_shifted_back(i));
+        let s = model(args, &z, &mut self.memory);
+        self.delay.push(s);
+        s
+    }
+}
+
+impl Default for UnanModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// memory holds 6 memories of state functions
+#[allow(unused_variables)]
+fn model(args: &[f32], z: &[f32], memory: &mut [Memory]) -> f32 {
+    let args: [f32; 3] = std::array::from_fn(|i| args[i].clamp(-1.0, 1.0));
+    let z: [f32; 2] = std::array::from_fn(|i| z[i].clamp(-1.0, 1.0));
+    let g0 = lag5(delay(and(rmin20(-0.19805646, &mut memory[0]), args[1]), &mut memory[1]), &mut memory[2]);
+    let g1 = and(sma20(max(0.6101861, args[2]), &mut memory[3]), diff(z[0], &mut memory[4]));
+    let g2 = sma2(lt(z[1], gt(args[0], args[0])), &mut memory[5]);
+    unan_c3(&[g0, g1, g2])
+}
+
+fn max(a0: f32, a1: f32) -> f32 {
+    a0.max(a1)
+}
+
+fn and(a0: f32, a1: f32) -> f32 {
+    from_truth(to_truth(a0)*to_truth(a1))
+}
+
+fn gt(a0: f32, a1: f32) -> f32 {
+    if a0 > a1 {1.0} else {-1.0}
+}
+
+fn lt(a0: f32, a1: f32) -> f32 {
+    if a0 < a1 {1.0} else {-1.0}
+}
+
+fn lag5(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, lag_op)
+}
+
+fn delay(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, delay_op)
+}
+
+fn rmin20(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, rmin_op)
+}
+
+fn sma20(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, sma_op)
+}
+
+fn diff(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, diff_op)
+}
+
+fn sma2(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, sma_op)
+}
+
+fn delay_op(_curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    prev.get_shifted_back(0)
+}
+
+fn diff_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    clip(curr - prev.get_shifted_back(0))
+}
+
+fn lag_op(_curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    prev.get_shifted_back(prev.capacity - 1)
+}
+
+fn sma_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    rolling(curr, prev).sum::<f32>() / prev.capacity as f32
+}
+
+fn rmin_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    rolling(curr, prev).fold(curr, f32::min)
+}
+
+fn unan_level(o: &[f32]) -> f32 {
+    if o.iter().all(|x| *x > 0.0) || o.iter().all(|x| *x < 0.0) {mean_of(o)} else {0.0}
+}
+
+fn unan_c3(o: &[f32]) -> f32 {
+    let x = unan_level(o).clamp(-1.0, 1.0);
+    (x * 3.0).round() / 3.0
+}
+
+fn clip(x: f32) -> f32 {
+    x.clamp(-1.0, 1.0)
+}
+
+fn to_truth(x: f32) -> f32 {
+    0.5*(x + 1.0)
+}
+
+fn from_truth(t: f32) -> f32 {
+    2.0*t - 1.0
+}
+
+fn mean_of(x: &[f32]) -> f32 {
+    x.iter().sum::<f32>() / x.len() as f32
+}
+
+fn rolling(curr: f32, prev: &DelayLine) -> impl Iterator<Item = f32> + '_ {
+    std::iter::once(curr).chain((0..prev.capacity - 1).map(move |k| prev.get_shifted_back(k)))
+}
+
+// memory of a state function: previous inputs and previous output
+#[allow(dead_code)]
+struct Memory {
+    inputs: DelayLine,
+    output: f32,
+}
+
+#[allow(dead_code)]
+impl Memory {
+    fn new(window: usize) -> Self {
+        Memory { inputs: DelayLine::new(window), output: 0.0 }
+    }
+
+    fn step(&mut self, curr: f32, op: fn(f32, &DelayLine, f32) -> f32) -> f32 {
+        let ret = op(curr, &self.inputs, self.output);
+        self.inputs.push(curr);
+        self.output = ret;
+        ret
+    }
+}
+
+struct DelayLine {
+    capacity: usize,
+    idx: usize,
+    buff: Vec<f32>,
+}
+
+impl DelayLine {
+    fn new(capacity: usize) -> Self {
+        let c = if capacity < 1 {1} else {capacity};
+        DelayLine { capacity: c, idx: 0, buff: vec![0.0; c] }
+    }
+
+    fn push(&mut self, v: f32) {
+        self.buff[self.idx] = v;
+        self.idx += 1;
+        if self.idx == self.capacity {self.idx = 0}
+    }
+
+    fn get_shifted_back(&self, steps: usize) -> f32 {
+        let i = (steps + 1) % self.capacity;
+        self.buff[if i > self.idx {self.idx + self.capacity - i} else {self.idx - i}]
+    }
+}
@@ -0,0 +1,193 @@
+// WSum[0.6095052, 0.89461756, -0.33011532](Delay(Ma2(Sma2(Diff(If(args[2], args[2], args[2]))))), Add(And(ZSc5(0.6539364), Neg(args[1])), Sgn(args[0])), Lag2(Thr(Mom3(z[1]), Lt(args[0], z[1]))))
+pub struct WSumModel {
+    memory: Vec<Memory>,
+    // previous outputs
+    delay: DelayLine,
+}
+
+impl WSumModel {
+    pub fn new() -> Self {
+        WSumModel { memory: vec![Memory::new(1), Memory::new(2), Memory::new(1), Memory::new(1), Memory::new(5), Memory::new(3), Memory::new(2)], delay: DelayLine::new(3) }
+    }
+
+    // output for a row of inputs
+    pub fn step(&mut self, args: &[f32]) -> f32 {
+        let z: [f32; 2] = std::array::from_fn(|i| self.delay.get_shifted_back(i));
+        let s = model(args, &z, &mut self.memory);
+        self.delay.push(s);
+        s
+    }
+}
+
+impl Default for WSumModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// memory holds 7 memories of state functions
+#[allow(unused_variables)]
+fn model(args: &[f32], z: &[f32], memory: &mut [Memory]) -> f32 {
+    let args: [f32; 3] = std::array::from_fn(|i| args[i].clamp(-1.0, 1.0));
+    let z: [f32; 2] = std::array::from_fn(|i| z[i].clamp(-1.0, 1.0));
+    let g0 = delay(ma2(sma2(diff(if_(args[2], args[2], args[2]), &mut memory[0]), &mut memory[1]), &mut memory[2]), &mut memory[3]);
+    let g1 = add(and(zsc5(0.6539364, &mut memory[4]), neg(args[1])), sgn(args[0]));
+    let g2 = lag2(thr(mom3(z[1], &mut memory[5]), lt(args[0], z[1])), &mut memory[6]);
+    wsum(&[g0, g1, g2], &[0.6095052, 0.89461756, -0.33011532])
+}
+
+fn neg(a0: f32) -> f32 {
+    -a0
+}
+
+fn sgn(a0: f32) -> f32 {
+    sign(a0)
+}
+
+fn thr(a0: f32, a1: f32) -> f32 {
+    if a0.abs() > a1.abs() {sign(a0)} else {0.0}
+}
+
+fn add(a0: f32, a1: f32) -> f32 {
+    clip(a0+a1)
+}
+
+fn and(a0: f32, a1: f32) -> f32 {
+    from_truth(to_truth(a0)*to_truth(a1))
+}
+
+fn lt(a0: f32, a1: f32) -> f32 {
+    if a0 < a1 {1.0} else {-1.0}
+}
+
+fn if_(a0: f32, a1: f32, a2: f32) -> f32 {
+    if a0 > 0.0 {a1} else {a2}
+}
+
+fn delay(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, delay_op)
+}
+
+fn ma2(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, ma2_op)
+}
+
+fn sma2(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, sma_op)
+}
+
+fn diff(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, diff_op)
+}
+
+fn zsc5(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, zsc_op)
+}
+
+fn lag2(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, lag_op)
+}
+
+fn mom3(x: f32, m: &mut Memory) -> f32 {
+    m.step(x, mom_op)
+}
+
+fn delay_op(_curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    prev.get_shifted_back(0)
+}
+
+fn ma2_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    0.5*(curr + prev.get_shifted_back(0))
+}
+
+fn diff_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    clip(curr - prev.get_shifted_back(0))
+}
+
+fn lag_op(_curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    prev.get_shifted_back(prev.capacity - 1)
+}
+
+fn sma_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    rolling(curr, prev).sum::<f32>() / prev.capacity as f32
+}
+
+fn mom_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    clip(curr - prev.get_shifted_back(prev.capacity - 1))
+}
+
+fn zsc_op(curr: f32, prev: &DelayLine, _out: f32) -> f32 {
+    let n = prev.capacity as f32;
+    let mean = rolling(curr, prev).sum::<f32>() / n;
+    let var = rolling(curr, prev).map(|x| (x - mean) * (x - mean)).sum::<f32>() / n;
+    if var <= 0.0 {0.0} else {clip(0.5 * (curr - mean) / var.sqrt())}
+}
+
+fn wsum(o: &[f32], w: &[f32]) -> f32 {
+    sign(o.iter().zip(w).map(|(o, w)| o * w).sum::<f32>())
+}
+
+fn clip(x: f32) -> f32 {
+    x.clamp(-1.0, 1.0)
+}
+
+fn sign(x: f32) -> f32 {
+    if x > 0.0 {1.0} else if x < 0.0 {-1.0} else {0.0}
+}
+
+fn to_truth(x: f32) -> f32 {
+    0.5*(x + 1.0)
+}
+
+fn from_truth(t: f32) -> f32 {
+    2.0*t - 1.0
+}
+
+fn rolling(curr: f32, prev: &DelayLine) -> impl Iterator<Item = f32> + '_ {
+    std::iter::once(curr).chain((0..prev.capacity - 1).map(move |k| prev.get_shifted_back(k)))
+}
+
+// memory of a state function: previous inputs and previous output
+#[allow(dead_code)]
+struct Memory {
+    inputs: DelayLine,
+    output: f32,
+}
+
+#[allow(dead_code)]
+impl Memory {
+    fn new(window: usize) -> Self {
+        Memory { inputs: DelayLine::new(window), output: 0.0 }
+    }
+
+    fn step(&mut self, curr: f32, op: fn(f32, &DelayLine, f32) -> f32) -> f32 {
+        let ret = op(curr, &self.inputs, self.output);
+        self.inputs.push(curr);
+        self.output = ret;
+        ret
+    }
+}
+
+struct DelayLine {
+    capacity: usize,
+    idx: usize,
+    buff: Vec<f32>,
+}
+
+impl DelayLine {
+    fn new(capacity: usize) -> Self {
+        let c = if capacity < 1 {1} else {capacity};
+        DelayLine { capacity: c, idx: 0, buff: vec![0.0; c] }
+    }
+
+    fn push(&mut self, v: f32) {
+        self.buff[self.idx] = v;
+        self.idx += 1;
+        if self.idx == self.capacity {self.idx = 0}
+    }
+
+    fn get_shifted_back(&self, steps: usize) -> f32 {
+        let i = (steps + 1) % self.capacity;
+        self.buff[if i > self.idx {self.idx + self.capacity - i} else {self.idx - i}]
+    }
+}